@@ -0,0 +1,381 @@
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path,
+};
+
+/// Supported archive formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl Format {
+    /// Guesses the format by the file name extension.
+    pub fn from_path(path: &path::Path) -> Option<Self> {
+        Self::split_name(path.file_name()?.to_str()?).map(|(_, format)| format)
+    }
+
+    /// The canonical extension, including the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::TarGz => ".tar.gz",
+            Format::TarZst => ".tar.zst",
+            Format::Zip => ".zip",
+        }
+    }
+
+    /// Splits an archive name into its stem and format.
+    /// Eg.: `foo.tar.gz` -> (`foo`, TarGz)
+    fn split_name(name: &str) -> Option<(&str, Self)> {
+        const EXTENSIONS: [(&str, Format); 5] = [
+            (".tar.gz", Format::TarGz),
+            (".tgz", Format::TarGz),
+            (".tar.zst", Format::TarZst),
+            (".tzst", Format::TarZst),
+            (".zip", Format::Zip),
+        ];
+
+        EXTENSIONS.iter().find_map(|(ext, format)| {
+            name.strip_suffix(ext)
+                .filter(|stem| !stem.is_empty())
+                .map(|stem| (stem, *format))
+        })
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(&self.extension()[1..])
+    }
+}
+
+/// Name of the archive without its extension.
+/// Used as the folder name when extracting into a subfolder.
+pub fn stem(path: &path::Path) -> Option<&str> {
+    Format::split_name(path.file_name()?.to_str()?).map(|(stem, _)| stem)
+}
+
+/// A file that will be written into the archive.
+struct Item {
+    path: path::PathBuf,
+    /// Path inside the archive.
+    name: path::PathBuf,
+    metadata: fs::Metadata,
+}
+
+/// Compresses `sources` into a new archive at `dest`.
+/// The entries are stored relative to `base`.
+/// `progress` is called with the amount of files written and the total.
+pub fn compress(
+    sources: &[path::PathBuf],
+    base: &path::Path,
+    dest: &path::Path,
+    format: Format,
+    progress: impl Fn(u64, u64),
+) -> io::Result<()> {
+    let mut items = Vec::new();
+    for source in sources {
+        let name = source.strip_prefix(base).unwrap_or(source).to_path_buf();
+        collect(source, name, dest, &mut items)?;
+    }
+
+    let file = fs::File::create_new(dest)?;
+    let total = items.len() as u64;
+    match format {
+        Format::TarGz => {
+            let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            write_tar(encoder, &items, progress)?.finish()?;
+        }
+        Format::TarZst => {
+            let encoder = zstd::Encoder::new(file, 0)?;
+            write_tar(encoder, &items, progress)?.finish()?;
+        }
+        Format::Zip => {
+            let mut zip = zip::ZipWriter::new(file);
+            for (i, item) in items.iter().enumerate() {
+                let name = item.name.to_string_lossy();
+                let options = zip::write::SimpleFileOptions::default()
+                    .unix_permissions(item.metadata.permissions().mode());
+                if item.metadata.is_dir() {
+                    zip.add_directory(name, options)?;
+                } else if item.metadata.is_symlink() {
+                    let target = fs::read_link(&item.path)?;
+                    zip.add_symlink(name, target.to_string_lossy(), options)?;
+                } else {
+                    zip.start_file(name, options)?;
+                    io::copy(&mut fs::File::open(&item.path)?, &mut zip)?;
+                }
+                progress(i as u64 + 1, total);
+            }
+            zip.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Walks `path` recursively, adding everything to `items`.
+/// `skip` is the archive being created, so we don't try to put it inside itself.
+fn collect(
+    path: &path::Path,
+    name: path::PathBuf,
+    skip: &path::Path,
+    items: &mut Vec<Item>,
+) -> io::Result<()> {
+    if path == skip {
+        return Ok(());
+    }
+
+    let metadata = fs::symlink_metadata(path)?;
+    let is_dir = metadata.is_dir();
+    items.push(Item {
+        path: path.to_path_buf(),
+        name: name.clone(),
+        metadata,
+    });
+
+    if is_dir {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            collect(&entry.path(), name.join(entry.file_name()), skip, items)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, items: &[Item], progress: impl Fn(u64, u64)) -> io::Result<W> {
+    let mut tar = tar::Builder::new(writer);
+    // Store the links themselves, not what they point to.
+    tar.follow_symlinks(false);
+
+    let total = items.len() as u64;
+    for (i, item) in items.iter().enumerate() {
+        if item.metadata.is_dir() {
+            tar.append_dir(&item.name, &item.path)?;
+        } else {
+            tar.append_path_with_name(&item.path, &item.name)?;
+        }
+        progress(i as u64 + 1, total);
+    }
+
+    tar.into_inner()
+}
+
+/// Extracts `archive` into the `dest` directory, creating it if needed.
+/// Files already there are not replaced, it fails instead.
+/// `progress` is called with the amount of work done and the total.
+pub fn extract(
+    archive: &path::Path,
+    dest: &path::Path,
+    progress: impl Fn(u64, u64),
+) -> io::Result<()> {
+    let format = Format::from_path(archive)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "unknown archive format"))?;
+
+    fs::create_dir_all(dest)?;
+
+    let file = fs::File::open(archive)?;
+    match format {
+        Format::TarGz | Format::TarZst => {
+            // We don't know how many entries there are without reading the whole thing,
+            // so report how much of the compressed file we have consumed.
+            let total = file.metadata()?.len();
+            let reader = ProgressReader {
+                inner: file,
+                read: 0,
+                total,
+                progress,
+            };
+            if format == Format::TarGz {
+                unpack_tar(flate2::read::GzDecoder::new(reader), dest)
+            } else {
+                unpack_tar(zstd::Decoder::new(reader)?, dest)
+            }
+        }
+        Format::Zip => {
+            let mut zip = zip::ZipArchive::new(file)?;
+            let total = zip.len() as u64;
+            // Where links are resolved to, to check nothing is written outside of it.
+            let real_dest = dest.canonicalize()?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i)?;
+                // Entries with paths escaping `dest` are skipped.
+                let Some(name) = entry.enclosed_name() else {
+                    continue;
+                };
+                let out = dest.join(&name);
+                unpack_zip_entry(&mut entry, &name, &out, &real_dest).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("could not extract `{name}`: {e}", name = name.display()),
+                    )
+                })?;
+                progress(i as u64 + 1, total);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Writes `entry` at `out`, refusing to replace what's already there.
+/// `name` is the path of the entry inside of the archive, `real_dest` the destination with its links resolved.
+fn unpack_zip_entry(
+    entry: &mut zip::read::ZipFile,
+    name: &path::Path,
+    out: &path::Path,
+    real_dest: &path::Path,
+) -> io::Result<()> {
+    create_parents(out, real_dest)?;
+    if entry.is_dir() {
+        fs::create_dir_all(out)?;
+    } else if entry.is_symlink() {
+        // The content of a link entry is its target.
+        let mut target = String::new();
+        entry.read_to_string(&mut target)?;
+        if !link_stays_inside(name, path::Path::new(&target)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the link points outside of the destination, to `{target}`"),
+            ));
+        }
+        std::os::unix::fs::symlink(target, out)?;
+    } else {
+        io::copy(entry, &mut fs::File::create_new(out)?)?;
+        if let Some(mode) = entry.unix_mode() {
+            fs::set_permissions(out, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates the missing parent directories of `path`.
+/// Fails if a link extracted before would take them outside of `real_dest`.
+fn create_parents(path: &path::Path, real_dest: &path::Path) -> io::Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    // Links can only be in the part that exists already.
+    if let Some(existing) = parent.ancestors().find(|p| p.exists()) {
+        if !existing.canonicalize()?.starts_with(real_dest) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "a link in the archive leads outside of the destination",
+            ));
+        }
+    }
+    fs::create_dir_all(parent)
+}
+
+/// If a link at `name`, relative to the destination, pointing to `target` stays inside of the destination.
+/// Absolute targets never do.
+fn link_stays_inside(name: &path::Path, target: &path::Path) -> bool {
+    let depth = |path: &path::Path, start: usize| {
+        path.components()
+            .try_fold(start, |depth, component| match component {
+                path::Component::Normal(_) => Some(depth + 1),
+                path::Component::CurDir => Some(depth),
+                path::Component::ParentDir => depth.checked_sub(1),
+                path::Component::RootDir | path::Component::Prefix(_) => None,
+            })
+    };
+    name.parent()
+        .map_or(Some(0), |parent| depth(parent, 0))
+        .and_then(|dir_depth| depth(target, dir_depth))
+        .is_some()
+}
+
+fn unpack_tar<R: Read>(reader: R, dest: &path::Path) -> io::Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    // Extracting into a directory with files is common, they are not replaced.
+    tar.set_overwrite(false);
+    for entry in tar.entries()? {
+        // `unpack_in` refuses paths that would escape `dest`.
+        entry?.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+/// Reports how many bytes were read from the inner reader.
+struct ProgressReader<R, F> {
+    inner: R,
+    read: u64,
+    total: u64,
+    progress: F,
+}
+
+impl<R: Read, F: Fn(u64, u64)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        (self.progress)(self.read, self.total);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn split_name_finds_the_format() {
+        assert_eq!(
+            Format::split_name("foo.tar.gz"),
+            Some(("foo", Format::TarGz))
+        );
+        assert_eq!(Format::split_name("foo.tgz"), Some(("foo", Format::TarGz)));
+        assert_eq!(
+            Format::split_name("a.b.tar.zst"),
+            Some(("a.b", Format::TarZst))
+        );
+        assert_eq!(
+            Format::split_name("foo.tzst"),
+            Some(("foo", Format::TarZst))
+        );
+        assert_eq!(Format::split_name("foo.zip"), Some(("foo", Format::Zip)));
+    }
+
+    #[test]
+    fn split_name_needs_a_stem_and_known_extension() {
+        assert_eq!(Format::split_name(".zip"), None);
+        assert_eq!(Format::split_name(".tar.gz"), None);
+        assert_eq!(Format::split_name("foo.tar"), None);
+        assert_eq!(Format::split_name("foo"), None);
+    }
+
+    #[test]
+    fn links_inside_are_allowed() {
+        assert!(link_stays_inside(Path::new("link"), Path::new("file")));
+        assert!(link_stays_inside(
+            Path::new("dir/link"),
+            Path::new("../file")
+        ));
+        assert!(link_stays_inside(
+            Path::new("dir/link"),
+            Path::new("./sub/../file")
+        ));
+    }
+
+    #[test]
+    fn links_outside_are_refused() {
+        assert!(!link_stays_inside(Path::new("link"), Path::new("../file")));
+        assert!(!link_stays_inside(
+            Path::new("dir/link"),
+            Path::new("../../file")
+        ));
+        assert!(!link_stays_inside(
+            Path::new("dir/link"),
+            Path::new("sub/../../../file")
+        ));
+        assert!(!link_stays_inside(
+            Path::new("link"),
+            Path::new("/etc/passwd")
+        ));
+    }
+}
@@ -0,0 +1,869 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    command_column::CommandValues,
+    config::Config,
+    file_info::{DateKind, FileInfo},
+    git::RepoStatus,
+    size::SizeUnits,
+    users::UserNames,
+    vterm::{Panel, VTerm},
+};
+
+const DEFAULT_DATE_FORMAT: &str = "%e %b %y";
+/// How wide dates are in `DEFAULT_DATE_FORMAT`.
+const DEFAULT_DATE_WIDTH: usize = 9;
+/// Date format for the time since the date, like `3m ago`.
+const RELATIVE: &str = "relative";
+
+/// What a column of the entries table shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKind {
+    Name,
+    Permissions,
+    Size,
+    LastModified,
+    LastChanged,
+    LastAccessed,
+    Birth,
+    Owner,
+    Group,
+    Uid,
+    Gid,
+    Inode,
+    Links,
+    /// Space actually used on disk, from the allocated blocks.
+    DiskSize,
+    /// Git status, for directories the most important one of their contents.
+    Git,
+    /// Defined by the user, the output of a command run for each entry.
+    Command,
+}
+
+impl ColumnKind {
+    /// The built in ones.
+    pub const ALL: [ColumnKind; 15] = [
+        ColumnKind::Name,
+        ColumnKind::Permissions,
+        ColumnKind::Size,
+        ColumnKind::LastModified,
+        ColumnKind::LastChanged,
+        ColumnKind::LastAccessed,
+        ColumnKind::Birth,
+        ColumnKind::Owner,
+        ColumnKind::Group,
+        ColumnKind::Uid,
+        ColumnKind::Gid,
+        ColumnKind::Inode,
+        ColumnKind::Links,
+        ColumnKind::DiskSize,
+        ColumnKind::Git,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnKind::Name => "name",
+            ColumnKind::Permissions => "permissions",
+            ColumnKind::Size => "size",
+            ColumnKind::LastModified => DateKind::Modified.name(),
+            ColumnKind::LastChanged => DateKind::Changed.name(),
+            ColumnKind::LastAccessed => DateKind::Accessed.name(),
+            ColumnKind::Birth => DateKind::Birth.name(),
+            ColumnKind::Owner => "owner",
+            ColumnKind::Group => "group",
+            ColumnKind::Uid => "uid",
+            ColumnKind::Gid => "gid",
+            ColumnKind::Inode => "inode",
+            ColumnKind::Links => "links",
+            ColumnKind::DiskSize => "disk",
+            ColumnKind::Git => "git",
+            ColumnKind::Command => "command",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Which date it shows, if it's a date column.
+    fn date(self) -> Option<DateKind> {
+        match self {
+            ColumnKind::LastModified => Some(DateKind::Modified),
+            ColumnKind::LastChanged => Some(DateKind::Changed),
+            ColumnKind::LastAccessed => Some(DateKind::Accessed),
+            ColumnKind::Birth => Some(DateKind::Birth),
+            _ => None,
+        }
+    }
+
+    /// `units` is how sizes are shown, exact bytes need more space.
+    fn default_width(self, units: SizeUnits) -> Width {
+        match self {
+            ColumnKind::Name => Width::Min(10),
+            ColumnKind::Permissions => Width::Fixed(10),
+            // Fits 1 TB, and more if there is space.
+            ColumnKind::Size | ColumnKind::DiskSize if units == SizeUnits::Bytes => Width::Min(13),
+            // Fits `999.9 KiB`.
+            ColumnKind::Size | ColumnKind::DiskSize => Width::Fixed(9),
+            ColumnKind::LastModified
+            | ColumnKind::LastChanged
+            | ColumnKind::LastAccessed
+            | ColumnKind::Birth => Width::Fixed(DEFAULT_DATE_WIDTH),
+            ColumnKind::Owner | ColumnKind::Group => Width::Fixed(9),
+            ColumnKind::Uid | ColumnKind::Gid => Width::Fixed(6),
+            ColumnKind::Inode => Width::Fixed(9),
+            ColumnKind::Links => Width::Fixed(3),
+            ColumnKind::Git => Width::Fixed(1),
+            ColumnKind::Command => Width::Min(8),
+        }
+    }
+
+    fn default_align(self) -> Align {
+        match self {
+            ColumnKind::Name
+            | ColumnKind::Permissions
+            | ColumnKind::LastModified
+            | ColumnKind::LastChanged
+            | ColumnKind::LastAccessed
+            | ColumnKind::Birth
+            | ColumnKind::Owner
+            | ColumnKind::Group
+            | ColumnKind::Git
+            | ColumnKind::Command => Align::Left,
+            ColumnKind::Size
+            | ColumnKind::DiskSize
+            | ColumnKind::Uid
+            | ColumnKind::Gid
+            | ColumnKind::Inode
+            | ColumnKind::Links => Align::Right,
+        }
+    }
+
+    fn default_priority(self) -> u32 {
+        match self {
+            // Never hidden.
+            ColumnKind::Name => u32::MAX,
+            ColumnKind::Size => 50,
+            ColumnKind::LastModified => 40,
+            ColumnKind::Git => 35,
+            ColumnKind::LastChanged => 14,
+            ColumnKind::LastAccessed => 13,
+            ColumnKind::Birth => 7,
+            ColumnKind::Permissions => 30,
+            ColumnKind::Owner => 20,
+            ColumnKind::Group => 19,
+            ColumnKind::DiskSize => 15,
+            ColumnKind::Links => 12,
+            ColumnKind::Command => 8,
+            ColumnKind::Uid => 11,
+            ColumnKind::Gid => 10,
+            ColumnKind::Inode => 5,
+        }
+    }
+
+    fn default_format(self) -> Option<String> {
+        self.date().map(|_| DEFAULT_DATE_FORMAT.to_owned())
+    }
+}
+
+impl fmt::Display for ColumnKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+/// How much of the row a column takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    /// Always this wide.
+    Fixed(usize),
+    /// At least this wide, grows with the space left like `Flex(1)`.
+    Min(usize),
+    /// Takes a share of the space left, proportional to its weight.
+    Flex(usize),
+}
+
+impl FromStr for Width {
+    type Err = String;
+
+    /// Eg.: `10`, `min:10`, `flex`, `flex:2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.parse::<usize>().map_err(|_| {
+                format!("invalid width `{s}`, expected eg. `10`, `min:10` or `flex:2`")
+            })
+        };
+        match s.split_once(':') {
+            Some(("min", n)) => parse(n).map(Width::Min),
+            Some(("flex", n)) => parse(n).map(|n| Width::Flex(n.max(1))),
+            None if s == "flex" => Ok(Width::Flex(1)),
+            _ => parse(s).map(Width::Fixed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+impl FromStr for Align {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Align::Left),
+            "right" => Ok(Align::Right),
+            _ => Err(format!("invalid align `{s}`, expected `left` or `right`")),
+        }
+    }
+}
+
+/// A column of the entries table and how to lay it out.
+pub struct Column {
+    pub kind: ColumnKind,
+    /// How it's called in the config and the prompt.
+    pub name: String,
+    /// For `ColumnKind::Command`, run for each entry with `{}` replaced by its path.
+    /// The path is quoted for where `{}` is, it can be written bare or in quotes.
+    pub command: Option<String>,
+    pub width: Width,
+    pub align: Align,
+    /// For dates a `strftime` format or `relative`, for the rest a template where `{}` is replaced by the value.
+    pub format: Option<String>,
+    /// For sizes.
+    pub units: SizeUnits,
+    /// When there is no space, the columns with lower priority are hidden first.
+    pub priority: u32,
+    /// The user wants it shown.
+    pub enabled: bool,
+    /// Enabled, and there was space for it in the last layout.
+    pub visible: bool,
+    pub panel: Panel,
+}
+
+impl Column {
+    pub fn new(kind: ColumnKind, vterm: Arc<Mutex<VTerm>>) -> Self {
+        Self {
+            kind,
+            name: kind.name().to_owned(),
+            command: None,
+            width: kind.default_width(SizeUnits::default()),
+            align: kind.default_align(),
+            format: kind.default_format(),
+            units: SizeUnits::default(),
+            priority: kind.default_priority(),
+            enabled: false,
+            visible: false,
+            panel: Panel::new(vterm),
+        }
+    }
+
+    /// A column showing the output of `command`.
+    pub fn with_command(name: &str, command: &str, vterm: Arc<Mutex<VTerm>>) -> Self {
+        Self {
+            name: name.to_owned(),
+            command: Some(command.to_owned()),
+            ..Self::new(ColumnKind::Command, vterm)
+        }
+    }
+
+    /// Changes a setting by its name, as written in the config file.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "width" => self.width = value.parse()?,
+            "align" => self.align = value.parse()?,
+            "units" => self.set_units(value.parse()?),
+            "priority" => {
+                self.priority = value
+                    .parse()
+                    .map_err(|_| format!("invalid priority `{value}`, expected a number"))?
+            }
+            "format" => {
+                if self.kind.date().is_some() && value != RELATIVE && !is_valid_strftime(value) {
+                    return Err(format!("invalid date format `{value}`"));
+                }
+                let was_default = self.width == self.default_width();
+                self.format = Some(value.to_owned());
+                if was_default {
+                    self.width = self.default_width();
+                }
+            }
+            "command" if self.kind == ColumnKind::Command => self.command = Some(value.to_owned()),
+            "command" => {
+                return Err(format!(
+                    "`{name}` is built in, only new columns can have a command",
+                    name = self.name
+                ))
+            }
+            _ => return Err(format!("unknown column setting `{key}`")),
+        }
+        Ok(())
+    }
+
+    /// Changes the units of sizes, and the width to fit them unless it was set.
+    pub fn set_units(&mut self, units: SizeUnits) {
+        let was_default = self.width == self.default_width();
+        self.units = units;
+        if was_default {
+            self.width = self.default_width();
+        }
+    }
+
+    /// Width for the units and the date format, when it's not set.
+    fn default_width(&self) -> Width {
+        match (self.kind.date(), self.format.as_deref()) {
+            (Some(_), Some(format)) => Width::Fixed(date_width(format)),
+            _ => self.kind.default_width(self.units),
+        }
+    }
+
+    /// Narrowest this column can be.
+    fn min_width(&self) -> usize {
+        match self.width {
+            Width::Fixed(n) | Width::Min(n) => n,
+            Width::Flex(_) => 1,
+        }
+    }
+
+    /// Share of the space left this column takes.
+    fn weight(&self) -> usize {
+        match self.width {
+            Width::Fixed(_) => 0,
+            Width::Min(_) => 1,
+            Width::Flex(weight) => weight,
+        }
+    }
+
+    /// The text shown in this column for `entry`, formatted but not aligned.
+    /// `None` while the value is still being computed.
+    pub fn value(
+        &self,
+        entry: &FileInfo,
+        users: &mut UserNames,
+        commands: &mut CommandValues,
+        git: Option<&RepoStatus>,
+    ) -> Option<String> {
+        if let Some(date_kind) = self.kind.date() {
+            let Some(date) = entry.date(date_kind) else {
+                return Some("-".to_owned());
+            };
+            return Some(
+                match self.format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT) {
+                    RELATIVE => relative_time(date, chrono::Local::now()),
+                    format => date.format(format).to_string(),
+                },
+            );
+        }
+
+        let value = match self.kind {
+            ColumnKind::Name => {
+                let mut name = entry.name().to_owned();
+                if let Some(indicator) = entry.file_type().indicator() {
+                    name.push(indicator);
+                }
+                if let Some(target) = entry.link_target() {
+                    name.push_str(" -> ");
+                    name.push_str(&target.to_string_lossy());
+                }
+                name
+            }
+            ColumnKind::Permissions => entry.mode_string(),
+            ColumnKind::Size => self.units.format(entry.size()),
+            ColumnKind::LastModified
+            | ColumnKind::LastChanged
+            | ColumnKind::LastAccessed
+            | ColumnKind::Birth => unreachable!("dates are formatted above"),
+            ColumnKind::Owner => users.user(entry.uid()),
+            ColumnKind::Group => users.group(entry.gid()),
+            ColumnKind::Uid => entry.uid().to_string(),
+            ColumnKind::Gid => entry.gid().to_string(),
+            ColumnKind::Inode => entry.inode().to_string(),
+            ColumnKind::Links => entry.links().to_string(),
+            ColumnKind::DiskSize => self.units.format(entry.disk_size()),
+            ColumnKind::Git => git
+                .and_then(|repo| repo.get(entry.path()))
+                .map(|status| status.symbol().to_string())
+                .unwrap_or_default(),
+            ColumnKind::Command => {
+                let command = self.command.as_deref().unwrap_or_default();
+                commands.get(command, entry)?.to_owned()
+            }
+        };
+
+        Some(match &self.format {
+            Some(template) => template.replace("{}", &value),
+            None => value,
+        })
+    }
+
+    /// Pads `text` to the width of the column, or cuts it with `...` if it doesn't fit.
+    pub fn align_text(&self, text: &str) -> String {
+        let width = self.panel.width;
+        if text.chars().count() > width {
+            let mut text = text
+                .chars()
+                .take(width.saturating_sub(3))
+                .collect::<String>();
+            text.push_str(&"..."[..width.min(3)]);
+            return text;
+        }
+
+        match self.align {
+            Align::Left => format!("{text:<width$}"),
+            Align::Right => format!("{text:>width$}"),
+        }
+    }
+}
+
+/// Builds every column, with the settings from the config.
+///
+/// ```text
+/// # For every size, unless a column sets its own.
+/// size_units = si
+///
+/// [columns]
+/// show = git name modified size permissions
+///
+/// [column.size]
+/// width = min:8
+/// align = left
+/// units = bytes
+/// priority = 10
+/// format = {} total
+///
+/// [column.accessed]
+/// format = relative
+///
+/// [column.mime]
+/// command = file --brief --mime-type {}
+/// ```
+/// Sections of columns that aren't built in define new ones, and must have a `command`.
+/// `size_units` is read by the caller, as it's not only for columns.
+/// Errors are returned along the columns, the settings with errors are left as default.
+pub fn from_config(
+    config: &Config,
+    size_units: SizeUnits,
+    vterm: Arc<Mutex<VTerm>>,
+) -> (Vec<Column>, Vec<String>) {
+    let mut columns = ColumnKind::ALL
+        .into_iter()
+        .map(|kind| Column::new(kind, vterm.clone()))
+        .collect::<Vec<_>>();
+    let mut errors = Vec::new();
+
+    let mut new_names = config
+        .section_names()
+        .filter_map(|section| section.strip_prefix("column."))
+        .filter(|name| ColumnKind::from_name(name).is_none())
+        .collect::<Vec<_>>();
+    new_names.sort();
+    for name in new_names {
+        match config.get(&format!("column.{name}"), "command") {
+            Some(command) => columns.push(Column::with_command(name, command, vterm.clone())),
+            None => errors.push(format!("[column.{name}] new columns need a `command`")),
+        }
+    }
+
+    let show = config
+        .get("columns", "show")
+        .unwrap_or("git name modified size permissions");
+    if let Err(e) = show_only(&mut columns, show) {
+        errors.push(e);
+        show_only(&mut columns, "name").expect("name column always exists");
+    }
+
+    for column in columns.iter_mut() {
+        column.set_units(size_units);
+        let section = format!("column.{name}", name = column.name);
+        for (key, value) in config.section(&section).into_iter().flatten() {
+            if let Err(e) = column.set(key, value) {
+                errors.push(format!("[{section}] {e}"));
+            }
+        }
+    }
+
+    (columns, errors)
+}
+
+fn find<'a>(columns: &'a mut [Column], name: &str) -> Result<&'a mut Column, String> {
+    columns
+        .iter_mut()
+        .find(|c| c.name == name)
+        .ok_or_else(|| format!("unknown column `{name}`"))
+}
+
+/// Shows only the columns in the space separated `names`, in that order.
+fn show_only(columns: &mut [Column], names: &str) -> Result<(), String> {
+    let names = names.split_whitespace().collect::<Vec<_>>();
+    for name in &names {
+        find(columns, name)?;
+    }
+    if !names.contains(&ColumnKind::Name.name()) {
+        return Err("the name column can't be hidden".to_owned());
+    }
+
+    // Stable, so the hidden ones keep their order.
+    columns.sort_by_key(|c| {
+        names
+            .iter()
+            .position(|name| *name == c.name)
+            .unwrap_or(usize::MAX)
+    });
+    for column in columns.iter_mut() {
+        column.enabled = names.contains(&column.name.as_str());
+    }
+    Ok(())
+}
+
+/// Changes the columns at runtime, returns a message for the user.
+/// - `size`: shows or hides the column.
+/// - `name size owner`: shows only these columns, in this order.
+/// - `size width=min:6 align=left`: changes the settings of a column, `format` must be the last one.
+/// - `mime command=file --brief --mime-type {}`: adds a new column, `command` must be the last one.
+pub fn command(
+    columns: &mut Vec<Column>,
+    text: &str,
+    vterm: Arc<Mutex<VTerm>>,
+) -> Result<String, String> {
+    let text = text.trim();
+    if !text.contains('=') {
+        let mut names = text.split_whitespace();
+        return match (names.next(), names.next()) {
+            (None, _) => Err("no column given".to_owned()),
+            (Some(name), None) => {
+                let column = find(columns, name)?;
+                if column.kind == ColumnKind::Name {
+                    return Err("the name column can't be hidden".to_owned());
+                }
+                column.enabled = !column.enabled;
+                Ok(format!(
+                    "{action} column {name}",
+                    action = if column.enabled { "Showing" } else { "Hiding" }
+                ))
+            }
+            (Some(_), Some(_)) => {
+                show_only(columns, text)?;
+                Ok(format!("Showing columns {text}"))
+            }
+        };
+    }
+
+    let (name, mut settings) = text.split_once(' ').unwrap_or((text, ""));
+    if find(columns, name).is_err() {
+        let Some((_, command)) = settings.split_once("command=") else {
+            return Err(format!(
+                "unknown column `{name}`, new columns need a `command=`"
+            ));
+        };
+        columns.push(Column::with_command(name, command.trim(), vterm));
+    }
+    let column = find(columns, name)?;
+
+    while !settings.trim().is_empty() {
+        let Some((key, rest)) = settings.split_once('=') else {
+            return Err(format!("expected `key=value`, got `{settings}`"));
+        };
+        let key = key.trim();
+        // Formats and commands can have spaces, so they take the rest of the line.
+        let (value, rest) = if key == "format" || key == "command" {
+            (rest, "")
+        } else {
+            rest.split_once(' ').unwrap_or((rest, ""))
+        };
+        column.set(key, value.trim())?;
+        settings = rest;
+    }
+    column.enabled = true;
+
+    Ok(format!("Updated column {name}"))
+}
+
+/// Places the enabled columns side by side, separated by a space, inside the given area.
+/// If they don't fit, the ones with lower priority are hidden.
+pub fn layout(columns: &mut [Column], x: usize, y: usize, width: usize, height: usize) {
+    for column in columns.iter_mut() {
+        column.visible = column.enabled;
+    }
+
+    let needed = |columns: &[Column]| {
+        let visible = columns.iter().filter(|c| c.visible);
+        visible.clone().map(Column::min_width).sum::<usize>() + visible.count().saturating_sub(1)
+    };
+    while needed(columns) > width {
+        let Some(least) = columns
+            .iter_mut()
+            .filter(|c| c.visible && c.kind != ColumnKind::Name)
+            .min_by_key(|c| c.priority)
+        else {
+            break;
+        };
+        least.visible = false;
+    }
+
+    // Share what is left between the columns that can grow.
+    let mut left = width.saturating_sub(needed(columns));
+    let total_weight = columns
+        .iter()
+        .filter(|c| c.visible)
+        .map(Column::weight)
+        .sum::<usize>();
+    let mut weight_left = total_weight;
+    let mut column_x = x;
+    for column in columns.iter_mut() {
+        if !column.visible {
+            column.panel.update_size(0, 0, 0, 0);
+            continue;
+        }
+
+        let mut column_width = column.min_width();
+        if column.weight() > 0 {
+            // The last one to grow takes what is left from rounding.
+            let extra = if column.weight() == weight_left {
+                left
+            } else {
+                left * column.weight() / weight_left
+            };
+            column_width += extra;
+            left -= extra;
+            weight_left -= column.weight();
+        }
+
+        let column_width = column_width.min((x + width).saturating_sub(column_x));
+        column.panel.update_size(column_x, y, column_width, height);
+        column_x += column_width + 1;
+    }
+}
+
+/// How wide dates are in `format`, a `strftime` one or `relative`.
+fn date_width(format: &str) -> usize {
+    if format == RELATIVE {
+        return "in the future".len();
+    }
+    // Long names of the month and the day, and two digits everywhere.
+    let sample = chrono::NaiveDate::from_ymd_opt(2020, 9, 30)
+        .and_then(|date| date.and_hms_opt(22, 22, 22))
+        .and_then(|date| date.and_local_timezone(chrono::Local).earliest());
+    sample.map_or(DEFAULT_DATE_WIDTH, |date| {
+        date.format(format).to_string().chars().count()
+    })
+}
+
+fn is_valid_strftime(format: &str) -> bool {
+    chrono::format::StrftimeItems::new(format)
+        .all(|item| !matches!(item, chrono::format::Item::Error))
+}
+
+/// How long ago `date` was, like `3m ago` or `yesterday`.
+pub fn relative_time(
+    date: chrono::DateTime<chrono::Local>,
+    now: chrono::DateTime<chrono::Local>,
+) -> String {
+    let elapsed = now.signed_duration_since(date);
+    if elapsed.num_seconds() < 0 {
+        return "in the future".to_owned();
+    }
+
+    let days_ago = (now.date_naive() - date.date_naive()).num_days();
+    match elapsed {
+        e if e.num_minutes() < 1 => "just now".to_owned(),
+        e if e.num_hours() < 1 => format!("{m}m ago", m = e.num_minutes()),
+        e if days_ago == 0 => format!("{h}h ago", h = e.num_hours()),
+        _ if days_ago == 1 => "yesterday".to_owned(),
+        _ if days_ago < 7 => format!("{days_ago}d ago"),
+        _ if days_ago < 30 => format!("{w}w ago", w = days_ago / 7),
+        _ if days_ago < 365 => format!("{m}mo ago", m = days_ago / 30),
+        _ => format!("{y}y ago", y = days_ago / 365),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        let vterm = Arc::new(Mutex::new(VTerm::detached(80, 24)));
+        from_config(&Config::default(), SizeUnits::default(), vterm).0
+    }
+
+    fn get<'a>(columns: &'a [Column], name: &str) -> &'a Column {
+        columns.iter().find(|c| c.name == name).unwrap()
+    }
+
+    fn get_mut<'a>(columns: &'a mut [Column], name: &str) -> &'a mut Column {
+        find(columns, name).unwrap()
+    }
+
+    /// Names of the visible columns and their widths, left to right.
+    fn widths(columns: &[Column]) -> Vec<(&str, usize)> {
+        columns
+            .iter()
+            .filter(|c| c.visible)
+            .map(|c| (c.name.as_str(), c.panel.width))
+            .collect()
+    }
+
+    #[test]
+    fn layout_gives_the_space_left_to_the_columns_that_grow() {
+        let mut columns = columns();
+        show_only(&mut columns, "name size permissions").unwrap();
+        layout(&mut columns, 0, 0, 40, 10);
+        // 9 and 10 for the fixed ones, 2 spaces between them.
+        assert_eq!(
+            widths(&columns),
+            [("name", 19), ("size", 9), ("permissions", 10)]
+        );
+    }
+
+    #[test]
+    fn layout_hides_the_lowest_priority_first() {
+        let mut columns = columns();
+        show_only(&mut columns, "name size permissions").unwrap();
+        get_mut(&mut columns, "size").priority = 0;
+        get_mut(&mut columns, "permissions").priority = 1;
+        layout(&mut columns, 0, 0, 22, 10);
+        assert_eq!(widths(&columns), [("name", 11), ("permissions", 10)]);
+
+        // The name is never hidden.
+        layout(&mut columns, 0, 0, 5, 10);
+        assert_eq!(widths(&columns), [("name", 5)]);
+    }
+
+    #[test]
+    fn layout_shares_by_weight() {
+        let mut columns = columns();
+        show_only(&mut columns, "name owner").unwrap();
+        get_mut(&mut columns, "name").width = Width::Flex(3);
+        get_mut(&mut columns, "owner").width = Width::Flex(1);
+        // They are at least 1 wide, what is left after that and the space is shared.
+        layout(&mut columns, 0, 0, 43, 10);
+        assert_eq!(widths(&columns), [("name", 31), ("owner", 11)]);
+    }
+
+    #[test]
+    fn command_toggles_a_column() {
+        let mut columns = columns();
+        let vterm = Arc::new(Mutex::new(VTerm::detached(80, 24)));
+        assert!(!get(&columns, "owner").enabled);
+        command(&mut columns, "owner", vterm.clone()).unwrap();
+        assert!(get(&columns, "owner").enabled);
+        command(&mut columns, "owner", vterm.clone()).unwrap();
+        assert!(!get(&columns, "owner").enabled);
+        assert!(command(&mut columns, "name", vterm.clone()).is_err());
+        assert!(command(&mut columns, "unknown", vterm).is_err());
+    }
+
+    #[test]
+    fn command_shows_only_the_given_columns() {
+        let mut columns = columns();
+        let vterm = Arc::new(Mutex::new(VTerm::detached(80, 24)));
+        command(&mut columns, "size name", vterm.clone()).unwrap();
+        let enabled = columns
+            .iter()
+            .filter(|c| c.enabled)
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(enabled, ["size", "name"]);
+        assert!(command(&mut columns, "size owner", vterm).is_err());
+    }
+
+    #[test]
+    fn command_changes_settings() {
+        let mut columns = columns();
+        let vterm = Arc::new(Mutex::new(VTerm::detached(80, 24)));
+        command(
+            &mut columns,
+            "size width=min:6 align=left format={} total",
+            vterm.clone(),
+        )
+        .unwrap();
+        let size = get(&columns, "size");
+        assert_eq!(size.width, Width::Min(6));
+        assert_eq!(size.align, Align::Left);
+        assert_eq!(size.format.as_deref(), Some("{} total"));
+        assert!(command(&mut columns, "size width=wide", vterm.clone()).is_err());
+        assert!(command(&mut columns, "size color=red", vterm).is_err());
+    }
+
+    #[test]
+    fn command_adds_command_columns() {
+        let mut columns = columns();
+        let vterm = Arc::new(Mutex::new(VTerm::detached(80, 24)));
+        command(&mut columns, "mime command=file -b {}", vterm.clone()).unwrap();
+        let mime = get(&columns, "mime");
+        assert_eq!(mime.kind, ColumnKind::Command);
+        assert_eq!(mime.command.as_deref(), Some("file -b {}"));
+        assert!(mime.enabled);
+        assert!(command(&mut columns, "other width=5", vterm.clone()).is_err());
+        assert!(command(&mut columns, "size command=wc -c {}", vterm).is_err());
+    }
+
+    #[test]
+    fn exact_sizes_get_wider_columns() {
+        let mut columns = columns();
+        let size = get_mut(&mut columns, "size");
+        size.set("units", "bytes").unwrap();
+        assert_eq!(size.width, Width::Min(13));
+        size.set("units", "si").unwrap();
+        assert_eq!(size.width, Width::Fixed(9));
+
+        // Unless the width was set.
+        size.set("width", "12").unwrap();
+        size.set("units", "bytes").unwrap();
+        assert_eq!(size.width, Width::Fixed(12));
+    }
+
+    #[test]
+    fn date_formats_get_columns_that_fit_them() {
+        let mut columns = columns();
+        let modified = get_mut(&mut columns, "modified");
+        assert_eq!(modified.width, Width::Fixed(9));
+        modified.set("format", "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(modified.width, Width::Fixed(16));
+        modified.set("format", "%A %e %B").unwrap();
+        assert_eq!(modified.width, Width::Fixed("Wednesday 30 September".len()));
+        modified.set("format", "relative").unwrap();
+        assert_eq!(modified.width, Width::Fixed(13));
+
+        // Unless the width was set.
+        modified.set("width", "min:12").unwrap();
+        modified.set("format", "%H:%M").unwrap();
+        assert_eq!(modified.width, Width::Min(12));
+    }
+
+    #[test]
+    fn relative_time_of_recent_dates() {
+        let now = chrono::Local
+            .with_ymd_and_hms(2024, 6, 15, 12, 0, 0)
+            .unwrap();
+        let ago = |duration| relative_time(now - duration, now);
+        assert_eq!(ago(chrono::Duration::seconds(30)), "just now");
+        assert_eq!(ago(chrono::Duration::minutes(5)), "5m ago");
+        assert_eq!(ago(chrono::Duration::hours(3)), "3h ago");
+        assert_eq!(
+            relative_time(now + chrono::Duration::hours(1), now),
+            "in the future"
+        );
+    }
+
+    #[test]
+    fn relative_time_of_older_dates_counts_days() {
+        let now = chrono::Local
+            .with_ymd_and_hms(2024, 6, 15, 1, 0, 0)
+            .unwrap();
+        let at = |y, m, d, h| chrono::Local.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap();
+        // Less than a day, but it was another day.
+        assert_eq!(relative_time(at(2024, 6, 14, 23), now), "yesterday");
+        assert_eq!(relative_time(at(2024, 6, 12, 12), now), "3d ago");
+        assert_eq!(relative_time(at(2024, 6, 1, 12), now), "2w ago");
+        assert_eq!(relative_time(at(2024, 3, 1, 12), now), "3mo ago");
+        assert_eq!(relative_time(at(2021, 6, 1, 12), now), "3y ago");
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    mem, path, process,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+};
+
+use crate::{file_info::FileInfo, shell};
+
+/// Most commands running at the same time.
+const MAX_WORKERS: usize = 4;
+
+/// Most values kept, past it only the ones of the directory being listed are.
+const CACHE_MAX: usize = 10_000;
+
+type Modified = chrono::DateTime<chrono::Local>;
+/// The command template and the path it's run for.
+type Key = (String, path::PathBuf);
+
+struct Request {
+    key: Key,
+    modified: Modified,
+}
+
+struct Response {
+    key: Key,
+    modified: Modified,
+    value: String,
+}
+
+/// Work shared with the threads running the commands.
+#[derive(Default)]
+struct Queue {
+    /// Only the rows drawn last, the first ones first.
+    requests: VecDeque<Request>,
+    running: HashSet<Key>,
+}
+
+/// Runs the commands of the user defined columns in a pool of background threads.
+/// Results are cached by command and path, and are valid while the entry modification time doesn't change.
+/// Only the rows drawn since the last poll are queued, rows scrolled past are dropped before their command runs.
+pub struct CommandValues {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    responses: mpsc::Receiver<Response>,
+    cache: HashMap<Key, (Modified, String)>,
+    /// Values asked for since the last poll.
+    wanted: Vec<Request>,
+}
+
+impl CommandValues {
+    pub fn new() -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (responses_tx, responses) = mpsc::channel();
+
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+        for _ in 0..workers {
+            let queue = queue.clone();
+            let responses_tx = responses_tx.clone();
+            thread::spawn(move || loop {
+                let (lock, ready) = &*queue;
+                let Request { key, modified } = {
+                    let mut queue = ready
+                        .wait_while(lock.lock().unwrap(), |q| q.requests.is_empty())
+                        .unwrap();
+                    let request = queue.requests.pop_front().expect("waited for one");
+                    queue.running.insert(request.key.clone());
+                    request
+                };
+                let value = run(&key.0, &key.1);
+                lock.lock().unwrap().running.remove(&key);
+                let response = Response {
+                    key,
+                    modified,
+                    value,
+                };
+                // Ends when `CommandValues` is dropped.
+                if responses_tx.send(response).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            queue,
+            responses,
+            cache: HashMap::new(),
+            wanted: Vec::new(),
+        }
+    }
+
+    /// Value of `command` for `entry`.
+    /// If it's not cached, it's queued on the next poll and `None` is returned until it's done.
+    pub fn get(&mut self, command: &str, entry: &FileInfo) -> Option<&str> {
+        let key = (command.to_owned(), entry.path().to_path_buf());
+        let valid = self
+            .cache
+            .get(&key)
+            .is_some_and(|(modified, _)| *modified == entry.last_modified());
+        if !valid {
+            if !self.wanted.iter().any(|r| r.key == key) {
+                self.wanted.push(Request {
+                    key,
+                    modified: entry.last_modified(),
+                });
+            }
+            return None;
+        }
+
+        self.cache.get(&key).map(|(_, value)| value.as_str())
+    }
+
+    /// Collects the values computed since the last poll,
+    /// and replaces what is queued with what was asked for since then.
+    pub fn poll(&mut self) {
+        while let Ok(Response {
+            key,
+            modified,
+            value,
+        }) = self.responses.try_recv()
+        {
+            if self.cache.len() >= CACHE_MAX {
+                let dir = key.1.parent().map(path::Path::to_path_buf);
+                self.cache
+                    .retain(|(_, path), _| path.parent() == dir.as_deref());
+                if self.cache.len() >= CACHE_MAX {
+                    self.cache.clear();
+                }
+            }
+            self.cache.insert(key, (modified, value));
+        }
+
+        let (lock, ready) = &*self.queue;
+        let mut queue = lock.lock().unwrap();
+        let mut wanted = mem::take(&mut self.wanted);
+        wanted.retain(|r| !queue.running.contains(&r.key));
+        queue.requests = wanted.into();
+        if !queue.requests.is_empty() {
+            ready.notify_all();
+        }
+    }
+}
+
+/// Runs `command` with `sh`, from the directory of `path`, replacing `{}` with `path`.
+/// The value is the first line of the output, or `?` if the command failed.
+fn run(command: &str, path: &path::Path) -> String {
+    let mut cmd = process::Command::new("sh");
+    cmd.arg("-c")
+        .arg(substitute(command, &path.to_string_lossy()))
+        .stdin(process::Stdio::null())
+        .stderr(process::Stdio::null());
+    if let Some(parent) = path.parent() {
+        cmd.current_dir(parent);
+    }
+
+    match cmd.output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_owned(),
+        _ => "?".to_owned(),
+    }
+}
+
+/// Replaces `{}` in `command` with `path`, quoted for where it is, so `{}`, `'{}'` and `"{}"` all work.
+fn substitute(command: &str, path: &str) -> String {
+    let mut substituted = String::with_capacity(command.len());
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut chars = command.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '{' && chars.peek() == Some(&'}') {
+            chars.next();
+            if in_single_quotes {
+                // Closes the quotes for an escaped `'`, and opens them again.
+                substituted.push_str(&path.replace('\'', r"'\''"));
+            } else if in_double_quotes {
+                substituted.push_str(&shell::escape_in_double_quotes(path));
+            } else {
+                substituted.push_str(&shell::quote(path));
+            }
+            continue;
+        }
+
+        substituted.push(ch);
+        match ch {
+            '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+            '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+            '\\' if !in_single_quotes => substituted.extend(chars.next()),
+            _ => {}
+        }
+    }
+    substituted
+}
@@ -0,0 +1,271 @@
+use std::{io, os::unix::process::CommandExt, process, sync::mpsc, thread};
+
+use crate::shell;
+
+pub type CommandId = usize;
+
+/// How many finished commands are kept to be listed.
+const FINISHED_MAX: usize = 50;
+
+/// How many chunks of output can be waiting to be shown, commands that print more are held until they are.
+const PENDING_MAX: usize = 1024;
+
+/// Messages sent from the threads reading the output of the commands.
+enum StreamEvent {
+    /// What was read at once, it can end in the middle of a line.
+    Chunk {
+        id: CommandId,
+        text: String,
+    },
+    /// The chunk was not valid UTF-8, `text` has the invalid parts replaced.
+    InvalidChunk {
+        id: CommandId,
+        text: String,
+    },
+    Closed {
+        id: CommandId,
+    },
+}
+
+/// What happened to the commands since the last poll.
+pub enum Update {
+    /// Some of what the command printed, as soon as it did, so it can end in the middle of a line.
+    Output {
+        id: CommandId,
+        text: String,
+    },
+    Finished {
+        id: CommandId,
+    },
+}
+
+/// A command started from command mode.
+pub struct Command {
+    pub id: CommandId,
+    pub pid: u32,
+    /// The command as it would be typed.
+    pub command: String,
+    /// `None` while running.
+    pub status: Option<process::ExitStatus>,
+    child: process::Child,
+    /// Stdout and stderr, until they are both closed it's still running.
+    open_streams: u8,
+    /// Some of what it printed was not valid UTF-8.
+    pub invalid_utf8: bool,
+}
+
+impl Command {
+    pub fn is_running(&self) -> bool {
+        self.status.is_none() || self.open_streams > 0
+    }
+
+    /// Eg.: `running`, `exit 1`, `killed by SIGINT`
+    pub fn describe_status(&self) -> String {
+        match self.status {
+            Some(status) if !self.is_running() => shell::describe_status(status),
+            _ => "running".to_owned(),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|s| s.success()) && !self.invalid_utf8
+    }
+}
+
+/// Runs commands in the background, streaming what they print.
+/// Each one gets its own process group, so signals reach all the processes of a pipeline.
+pub struct Commands {
+    tx: mpsc::SyncSender<StreamEvent>,
+    rx: mpsc::Receiver<StreamEvent>,
+    /// Oldest first, running or not.
+    list: Vec<Command>,
+    next_id: CommandId,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::sync_channel(PENDING_MAX);
+        Self {
+            tx,
+            rx,
+            list: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Starts `command` without waiting for it.
+    /// Its input is closed, so it can't take the keys of the terminal, and it ends instead of waiting if it reads it.
+    pub fn spawn(&mut self, command: &mut process::Command) -> Result<CommandId, String> {
+        let pretty_command = shell::pretty(command);
+        let mut child = command
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|e| format!("Tried to run `{pretty_command}`, but failed because {e}"))?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(stdout) = child.stdout.take() {
+            self.stream(id, stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.stream(id, stderr);
+        }
+
+        self.list.push(Command {
+            id,
+            pid: child.id(),
+            command: pretty_command,
+            status: None,
+            child,
+            open_streams: 2,
+            invalid_utf8: false,
+        });
+        self.forget_finished();
+        Ok(id)
+    }
+
+    /// Sends what is printed to `stream` to the UI as it comes, from a new thread.
+    fn stream(&self, id: CommandId, mut stream: impl io::Read + Send + 'static) {
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let mut buf = [0; 8192];
+            // What was read and not sent yet.
+            let mut pending = Vec::new();
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(read) => pending.extend_from_slice(&buf[..read]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+                let event = match std::str::from_utf8(&pending) {
+                    Ok(text) => StreamEvent::Chunk {
+                        id,
+                        text: text.to_owned(),
+                    },
+                    // It ends in the middle of a character, the rest of it comes with the next read.
+                    Err(e) if e.error_len().is_none() => StreamEvent::Chunk {
+                        id,
+                        text: String::from_utf8_lossy(&pending[..e.valid_up_to()]).into_owned(),
+                    },
+                    Err(_) => StreamEvent::InvalidChunk {
+                        id,
+                        text: String::from_utf8_lossy(&pending).into_owned(),
+                    },
+                };
+                match &event {
+                    StreamEvent::Chunk { text, .. } if text.is_empty() => continue,
+                    StreamEvent::Chunk { text, .. } => pending.drain(..text.len()),
+                    _ => pending.drain(..),
+                };
+                // If the UI is gone there is no one to show it to.
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            if !pending.is_empty() {
+                let text = String::from_utf8_lossy(&pending).into_owned();
+                let _ = tx.send(StreamEvent::InvalidChunk { id, text });
+            }
+            let _ = tx.send(StreamEvent::Closed { id });
+        });
+    }
+
+    pub fn get(&self, id: CommandId) -> Option<&Command> {
+        self.list.iter().find(|c| c.id == id)
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> &[Command] {
+        &self.list
+    }
+
+    pub fn running(&self) -> impl Iterator<Item = &Command> {
+        self.list.iter().filter(|c| c.is_running())
+    }
+
+    /// Sends `SIGINT` to the processes of the command, like Ctrl-C in a shell.
+    pub fn interrupt(&self, id: CommandId) -> Result<(), String> {
+        let Some(command) = self.get(id).filter(|c| c.is_running()) else {
+            return Err("it's not running".to_owned());
+        };
+        signal_group(command.pid, libc::SIGINT).map_err(|e| e.to_string())
+    }
+
+    /// Collects what the commands printed, and which ones ended.
+    /// At most `PENDING_MAX` chunks at once, so a command that prints a lot doesn't stall the UI.
+    pub fn poll(&mut self) -> Vec<Update> {
+        let mut updates = Vec::new();
+        for event in self.rx.try_iter().take(PENDING_MAX) {
+            let id = match &event {
+                StreamEvent::Chunk { id, .. }
+                | StreamEvent::InvalidChunk { id, .. }
+                | StreamEvent::Closed { id } => *id,
+            };
+            let Some(command) = self.list.iter_mut().find(|c| c.id == id) else {
+                continue;
+            };
+            match event {
+                StreamEvent::Chunk { text, .. } => updates.push(Update::Output { id, text }),
+                StreamEvent::InvalidChunk { text, .. } => {
+                    command.invalid_utf8 = true;
+                    updates.push(Update::Output { id, text });
+                }
+                StreamEvent::Closed { .. } => {
+                    command.open_streams -= 1;
+                    if !command.is_running() {
+                        updates.push(Update::Finished { id });
+                    }
+                }
+            }
+        }
+
+        for command in self.list.iter_mut().filter(|c| c.status.is_none()) {
+            // An error means it was already waited for, so it's gone either way.
+            match command.child.try_wait() {
+                Ok(Some(status)) => command.status = Some(status),
+                Ok(None) => continue,
+                Err(_) => command.status = Some(process::ExitStatus::default()),
+            }
+            if !command.is_running() {
+                updates.push(Update::Finished { id: command.id });
+            }
+        }
+
+        updates
+    }
+
+    fn forget_finished(&mut self) {
+        let finished = self.list.iter().filter(|c| !c.is_running()).count();
+        let mut to_forget = finished.saturating_sub(FINISHED_MAX);
+        self.list.retain(|c| {
+            let forget = to_forget > 0 && !c.is_running();
+            if forget {
+                to_forget -= 1;
+            }
+            !forget
+        });
+    }
+}
+
+impl Drop for Commands {
+    /// Commands still running would outlive us, as if the terminal was closed they are sent `SIGHUP`.
+    fn drop(&mut self) {
+        for command in self.list.iter().filter(|c| c.status.is_none()) {
+            let _ = signal_group(command.pid, libc::SIGHUP);
+        }
+    }
+}
+
+/// Sends `signal` to the process group led by `pid`.
+fn signal_group(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    let pgid = libc::pid_t::try_from(pid).map_err(io::Error::other)?;
+    // SAFETY: `kill` doesn't touch our memory, the worst it can do is fail.
+    if unsafe { libc::kill(-pgid, signal) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use std::{collections::BTreeSet, env, fs, os::unix::fs::PermissionsExt, path};
+
+use crate::shell;
+
+/// Built-in commands of the shell, for the lines run through `$SHELL`.
+const SHELL_BUILTINS: [&str; 20] = [
+    "alias", "bg", "cd", "command", "echo", "eval", "exec", "exit", "export", "fg", "jobs",
+    "printf", "read", "set", "source", "test", "type", "umask", "unset", "wait",
+];
+
+/// What completing a line of command mode gives.
+pub struct Completion {
+    /// The line with its last word completed as much as it can be.
+    pub line: String,
+    /// What the word could be, when there is more than one.
+    pub candidates: Vec<String>,
+}
+
+/// The last word of a line, as the shell would see it.
+struct Word {
+    /// Byte of the line where it starts.
+    start: usize,
+    /// Without quotes nor escapes.
+    text: String,
+    /// It's where the name of a command goes: the first word, or after a `|`, `&`, `;` or `(`.
+    is_command: bool,
+}
+
+/// Completes the last word of `line`: with the name of a command in `$PATH` or of a shell built-in if it's the first one,
+/// or else with the path of a file, relative to `dir`.
+/// What's added is quoted for the shell.
+/// `None` if there is nothing to complete it with.
+pub fn complete(line: &str, dir: &path::Path) -> Option<Completion> {
+    let (rest, _) = shell::strip_interactive_prefix(line);
+    let mut word = last_word(rest);
+    word.start += line.len() - rest.len();
+
+    // `(name, is_dir)`, sorted.
+    let matches: Vec<(String, bool)> = if word.is_command && !word.text.contains('/') {
+        commands(&word.text)
+            .into_iter()
+            .map(|c| (c, false))
+            .collect()
+    } else {
+        files(&word.text, dir)
+    };
+
+    let (dir_part, _) = split_dir(&word.text);
+    let completed = match matches.as_slice() {
+        [] => return None,
+        [(name, is_dir)] => {
+            let end = if *is_dir { "/" } else { " " };
+            format!(
+                "{path}{end}",
+                path = quote_path(&format!("{dir_part}{name}"))
+            )
+        }
+        [(first, _), others @ ..] => {
+            let common = others.iter().fold(first.as_str(), |common, (name, _)| {
+                common_prefix(common, name)
+            });
+            let path = format!("{dir_part}{common}");
+            // Not to quote what was typed if nothing is added to it.
+            if path == word.text {
+                line[word.start..].to_owned()
+            } else {
+                quote_path(&path)
+            }
+        }
+    };
+
+    let candidates = if matches.len() > 1 {
+        matches
+            .into_iter()
+            .map(|(name, is_dir)| if is_dir { name + "/" } else { name })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(Completion {
+        line: format!("{typed}{completed}", typed = &line[..word.start]),
+        candidates,
+    })
+}
+
+fn last_word(line: &str) -> Word {
+    let mut word = Word {
+        start: 0,
+        text: String::new(),
+        is_command: true,
+    };
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        let next_start = i + ch.len_utf8();
+        match ch {
+            _ if in_single_quotes => {
+                in_single_quotes = ch != '\'';
+                if in_single_quotes {
+                    word.text.push(ch);
+                }
+            }
+            '"' => in_double_quotes = !in_double_quotes,
+            '\\' if in_double_quotes => match chars.peek() {
+                Some(&(_, next @ ('"' | '\\' | '$' | '`'))) => {
+                    word.text.push(next);
+                    chars.next();
+                }
+                _ => word.text.push(ch),
+            },
+            _ if in_double_quotes => word.text.push(ch),
+            '\'' => in_single_quotes = true,
+            '\\' => {
+                if let Some((_, next)) = chars.next() {
+                    word.text.push(next);
+                }
+            }
+            ch if ch.is_whitespace() => {
+                // Only a word that ended makes the next one an argument.
+                if word.start < i {
+                    word.is_command = false;
+                }
+                word.start = next_start;
+                word.text.clear();
+            }
+            '|' | '&' | ';' | '(' | '<' | '>' | ')' => {
+                // What comes after a redirection is a file.
+                word.is_command = matches!(ch, '|' | '&' | ';' | '(');
+                word.start = next_start;
+                word.text.clear();
+            }
+            ch => word.text.push(ch),
+        }
+    }
+    word
+}
+
+/// Programs in `$PATH` and shell built-ins, starting with `prefix`.
+fn commands(prefix: &str) -> Vec<String> {
+    let mut names: BTreeSet<String> = SHELL_BUILTINS
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&path_var) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !name.starts_with(prefix) || names.contains(&name) {
+                continue;
+            }
+            // Following links, most of `/usr/bin` can be links.
+            let is_executable = fs::metadata(entry.path())
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+            if is_executable {
+                names.insert(name);
+            }
+        }
+    }
+    names.into_iter().collect()
+}
+
+/// Names of the files `path` could be the start of, and if they are directories, sorted.
+/// Hidden files only if the name starts with a `.`.
+fn files(path: &str, dir: &path::Path) -> Vec<(String, bool)> {
+    let (dir_part, prefix) = split_dir(path);
+    let dir = match dir_part.strip_prefix("~/") {
+        Some(rest) => match env::var_os("HOME") {
+            Some(home) => path::Path::new(&home).join(rest),
+            None => return Vec::new(),
+        },
+        None => dir.join(dir_part),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<(String, bool)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = fs::metadata(entry.path()).is_ok_and(|m| m.is_dir());
+            Some((name, is_dir))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// `dir/sub/na` is `dir/sub/` and `na`.
+fn split_dir(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    }
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or_else(|| a.len().min(b.len()), |((i, _), _)| i);
+    &a[..len]
+}
+
+/// Quotes `path` for command mode, leaving a leading `~/` so it's still the home directory.
+fn quote_path(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some("") => "~/".to_owned(),
+        Some(rest) => format!("~/{rest}", rest = quote(rest)),
+        None => quote(path),
+    }
+}
+
+/// Like `shell::quote`, but quoting `%` too, so it's not taken as a placeholder.
+fn quote(s: &str) -> String {
+    if s.contains('%') {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    } else {
+        shell::quote(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(line: &str) -> (usize, String, bool) {
+        let word = last_word(line);
+        (word.start, word.text, word.is_command)
+    }
+
+    #[test]
+    fn last_word_of_the_line() {
+        assert_eq!(word(""), (0, "".into(), true));
+        assert_eq!(word("gi"), (0, "gi".into(), true));
+        assert_eq!(word("git "), (4, "".into(), false));
+        assert_eq!(word("  git st"), (6, "st".into(), false));
+    }
+
+    #[test]
+    fn last_word_without_quotes_nor_escapes() {
+        assert_eq!(word("cat 'my fi"), (4, "my fi".into(), false));
+        assert_eq!(word("cat \"a \\\"b"), (4, "a \"b".into(), false));
+        assert_eq!(word("cat my\\ fi"), (4, "my fi".into(), false));
+        assert_eq!(word("cat 'a'\"b\"c"), (4, "abc".into(), false));
+    }
+
+    #[test]
+    fn last_word_is_a_command_after_separators() {
+        assert_eq!(word("ls | gr"), (5, "gr".into(), true));
+        assert_eq!(word("make && ca"), (8, "ca".into(), true));
+        assert_eq!(word("a;b"), (2, "b".into(), true));
+        assert_eq!(word("(cd"), (1, "cd".into(), true));
+        // What comes after a redirection is a file.
+        assert_eq!(word("sort >ou"), (6, "ou".into(), false));
+        assert_eq!(word("ls | "), (5, "".into(), true));
+    }
+
+    #[test]
+    fn common_prefix_of_names() {
+        assert_eq!(common_prefix("main.rs", "mail.rs"), "mai");
+        assert_eq!(common_prefix("abc", "abcdef"), "abc");
+        assert_eq!(common_prefix("abcdef", "abc"), "abc");
+        assert_eq!(common_prefix("abc", "xyz"), "");
+        assert_eq!(common_prefix("añb", "añc"), "añ");
+        assert_eq!(common_prefix("ñ", "ó"), "");
+    }
+
+    #[test]
+    fn quote_path_keeps_the_home() {
+        assert_eq!(quote_path("~/my dir/"), "~/'my dir/'");
+        assert_eq!(quote_path("~/"), "~/");
+        assert_eq!(quote_path("src/main.rs"), "src/main.rs");
+        assert_eq!(quote_path("100%"), "'100%'");
+    }
+}
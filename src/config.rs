@@ -0,0 +1,133 @@
+use std::{collections::HashMap, env, fs, io, path};
+
+/// Settings read from `$XDG_CONFIG_HOME/dune/config` (`~/.config/dune/config` by default).
+///
+/// The file is INI like:
+/// ```text
+/// # Comment
+/// [section]
+/// key = value
+/// ```
+/// Keys before the first section belong to the `""` section.
+#[derive(Debug, Default)]
+pub struct Config {
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Config {
+    pub fn path() -> Option<path::PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(path::PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| path::Path::new(&home).join(".config")))?;
+        Some(config_home.join("dune").join("config"))
+    }
+
+    /// Reads the config file.
+    /// Not having one is the same as having an empty one.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| format!("{path}: {e}", path = path.to_string_lossy())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{path}: {e}", path = path.to_string_lossy())),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut section = String::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_owned();
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!(
+                    "line {n}: expected `key = value`, got `{line}`",
+                    n = i + 1
+                ));
+            };
+            config
+                .sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+
+        Ok(config)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section)?.get(key).map(String::as_str)
+    }
+
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    pub fn section(&self, section: &str) -> Option<&HashMap<String, String>> {
+        self.sections.get(section)
+    }
+}
+
+/// `true`, `yes` or `on`, and `false`, `no` or `off`.
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!(
+            "invalid value `{value}`, expected `true` or `false`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_sections() {
+        let config = Config::parse(
+            "# Comment\n\
+             size_units = si\n\
+             \n\
+             [columns]\n\
+             show = name size\n\
+             [ column.size ]\n\
+             \x20 width = min:8 \n\
+             format = {} = total\n",
+        )
+        .unwrap();
+        assert_eq!(config.get("", "size_units"), Some("si"));
+        assert_eq!(config.get("columns", "show"), Some("name size"));
+        assert_eq!(config.get("column.size", "width"), Some("min:8"));
+        // Only the first `=` separates the key.
+        assert_eq!(config.get("column.size", "format"), Some("{} = total"));
+        assert_eq!(config.get("columns", "width"), None);
+        assert_eq!(config.get("missing", "show"), None);
+    }
+
+    #[test]
+    fn parse_keeps_the_last_repeated_key() {
+        let config = Config::parse("[a]\nkey = 1\nkey = 2\n").unwrap();
+        assert_eq!(config.get("a", "key"), Some("2"));
+    }
+
+    #[test]
+    fn parse_fails_on_lines_without_value() {
+        let err = Config::parse("[a]\nkey = 1\nnot a setting\n").unwrap_err();
+        assert_eq!(err, "line 3: expected `key = value`, got `not a setting`");
+    }
+}
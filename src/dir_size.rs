@@ -0,0 +1,135 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io, path,
+    sync::mpsc,
+    thread,
+};
+
+use crate::file_info::FileInfo;
+
+type Modified = chrono::DateTime<chrono::Local>;
+
+struct Request {
+    path: path::PathBuf,
+    modified: Modified,
+}
+
+struct Response {
+    path: path::PathBuf,
+    modified: Modified,
+    size: io::Result<u64>,
+}
+
+/// Calculates the recursive size of directories in a background thread.
+/// Results are cached by path, and are valid while the directory modification time doesn't change.
+/// That time only changes when entries are added to or removed from the directory itself,
+/// so changes deeper in the tree are not noticed until a `recalculate`.
+pub struct DirSizes {
+    requests: mpsc::Sender<Request>,
+    responses: mpsc::Receiver<Response>,
+    cache: HashMap<path::PathBuf, (Modified, u64)>,
+    pending: HashSet<path::PathBuf>,
+    /// Calculate the sizes every time the entries are updated.
+    pub auto: bool,
+}
+
+impl DirSizes {
+    pub fn new() -> Self {
+        let (requests, requests_rx) = mpsc::channel::<Request>();
+        let (responses_tx, responses) = mpsc::channel();
+
+        thread::spawn(move || {
+            // Ends when `DirSizes` is dropped.
+            for Request { path, modified } in requests_rx {
+                let size = calculate(&path);
+                let response = Response {
+                    path,
+                    modified,
+                    size,
+                };
+                if responses_tx.send(response).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            responses,
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            auto: false,
+        }
+    }
+
+    /// Cached size of the directory, if it's still valid.
+    pub fn get(&self, entry: &FileInfo) -> Option<u64> {
+        self.cache
+            .get(entry.path())
+            .filter(|(modified, _)| *modified == entry.last_modified())
+            .map(|(_, size)| *size)
+    }
+
+    pub fn is_pending(&self, entry: &FileInfo) -> bool {
+        self.pending.contains(entry.path())
+    }
+
+    /// Queues the calculation of the size of `entry`.
+    /// Does nothing if it's not a directory, or if it is already cached or being calculated.
+    /// Links to directories are not followed.
+    pub fn request(&mut self, entry: &FileInfo) {
+        if !entry.is_dir()
+            || entry.is_symlink()
+            || self.get(entry).is_some()
+            || self.is_pending(entry)
+        {
+            return;
+        }
+
+        self.pending.insert(entry.path().to_path_buf());
+        let _ = self.requests.send(Request {
+            path: entry.path().to_path_buf(),
+            modified: entry.last_modified(),
+        });
+    }
+
+    /// Queues the calculation of the size of `entry` even if it's cached, for changes the cache can't notice.
+    pub fn recalculate(&mut self, entry: &FileInfo) {
+        self.cache.remove(entry.path());
+        self.request(entry);
+    }
+
+    /// Collects the sizes calculated since the last poll.
+    /// Directories that failed (eg.: no permission) are left out.
+    pub fn poll(&mut self) -> Vec<(path::PathBuf, u64)> {
+        let mut sizes = Vec::new();
+        while let Ok(Response {
+            path,
+            modified,
+            size,
+        }) = self.responses.try_recv()
+        {
+            self.pending.remove(&path);
+            if let Ok(size) = size {
+                self.cache.insert(path.clone(), (modified, size));
+                sizes.push((path, size));
+            }
+        }
+        sizes
+    }
+}
+
+/// Sums the size of everything inside `path`, without following symlinks.
+/// Like `du`, whatever can't be read inside of it is skipped.
+pub fn calculate(path: &path::Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = metadata.len();
+    for entry in fs::read_dir(path)?.flatten() {
+        size += calculate(&entry.path()).unwrap_or(0);
+    }
+    Ok(size)
+}
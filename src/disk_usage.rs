@@ -0,0 +1,220 @@
+use std::{cmp, fs, io, os::unix::fs::MetadataExt, path, sync::mpsc, thread};
+
+use crate::{jobs::JobId, ScrollingWindow};
+
+/// How many entries to scan between progress reports.
+const PROGRESS_EVERY: u64 = 512;
+
+/// A file or directory in the scanned tree.
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub path: path::PathBuf,
+    /// Space taken on the disk, for directories including everything inside.
+    pub size: u64,
+    /// Amount of entries inside, recursively.
+    pub items: u64,
+    pub is_dir: bool,
+    /// Sorted by size, biggest first.
+    pub children: Vec<Node>,
+}
+
+enum ScanEvent {
+    Progress(u64),
+    Done(io::Result<Node>),
+}
+
+/// Like `ncdu`, scans a subtree once and allows navigating it by size.
+pub struct DiskUsage {
+    rx: mpsc::Receiver<ScanEvent>,
+    /// How many entries were scanned so far.
+    pub scanned: u64,
+    tree: Option<Node>,
+    /// Indices of the children from the root to the node being viewed.
+    stack: Vec<usize>,
+    pub window: ScrollingWindow,
+    /// Jobs deleting entries, they are removed from the tree once they finish.
+    deleting: Vec<(JobId, path::PathBuf)>,
+}
+
+impl DiskUsage {
+    /// Starts scanning `path` in the background.
+    pub fn scan(path: path::PathBuf, window_len: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut scanned = 0;
+            let tree = scan(&path, &mut scanned, &tx);
+            let _ = tx.send(ScanEvent::Done(tree));
+        });
+
+        Self {
+            rx,
+            scanned: 0,
+            tree: None,
+            stack: Vec::new(),
+            window: ScrollingWindow::new(0, window_len),
+            deleting: Vec::new(),
+        }
+    }
+
+    /// Checks on the scan.
+    /// Returns the result of the scan once it's done.
+    pub fn poll(&mut self) -> Option<io::Result<()>> {
+        while let Ok(evt) = self.rx.try_recv() {
+            match evt {
+                ScanEvent::Progress(scanned) => self.scanned = scanned,
+                ScanEvent::Done(tree) => {
+                    return Some(tree.map(|tree| {
+                        self.scanned = tree.items;
+                        self.window.reset(tree.children.len());
+                        self.tree = Some(tree);
+                    }));
+                }
+            }
+        }
+        None
+    }
+
+    /// The directory being viewed.
+    pub fn node(&self) -> Option<&Node> {
+        let mut node = self.tree.as_ref()?;
+        for &i in &self.stack {
+            node = &node.children[i];
+        }
+        Some(node)
+    }
+
+    pub fn selected(&self) -> Option<&Node> {
+        self.node()?.children.get(self.window.selected())
+    }
+
+    /// Views the selected directory.
+    pub fn enter(&mut self) {
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        if !selected.is_dir {
+            return;
+        }
+
+        let len = selected.children.len();
+        self.stack.push(self.window.selected());
+        self.window.reset(len);
+    }
+
+    /// Views the parent directory, up to the root of the scan.
+    pub fn leave(&mut self) {
+        let Some(child) = self.stack.pop() else {
+            return;
+        };
+
+        let len = self.node().map(|n| n.children.len()).unwrap_or(0);
+        self.window.reset(len);
+        self.window.select(child);
+    }
+
+    /// Keeps track of the job `id` deleting `path`.
+    pub fn delete_started(&mut self, id: JobId, path: path::PathBuf) {
+        self.deleting.push((id, path));
+    }
+
+    /// Removes what the job `id` deleted from the tree, if it was one of ours and it succeeded.
+    pub fn delete_finished(&mut self, id: JobId, success: bool) {
+        let Some(idx) = self.deleting.iter().position(|(job, _)| *job == id) else {
+            return;
+        };
+        let (_, path) = self.deleting.remove(idx);
+        if success {
+            self.remove(&path);
+        }
+    }
+
+    /// Removes the node at `path` from the tree, and its size from the directories above it.
+    fn remove(&mut self, path: &path::Path) {
+        let Some(root) = self.tree.as_mut() else {
+            return;
+        };
+
+        // Indices of the children from the root to the node.
+        let mut chain = Vec::new();
+        let mut node = &*root;
+        while node.path != path {
+            let Some(i) = node.children.iter().position(|c| path.starts_with(&c.path)) else {
+                return;
+            };
+            chain.push(i);
+            node = &node.children[i];
+        }
+        // The root is not deleted from here.
+        let Some((&idx, parents)) = chain.split_last() else {
+            return;
+        };
+        let (size, items) = (node.size, node.items + 1);
+
+        let mut node = root;
+        node.size -= size;
+        node.items -= items;
+        for &i in parents {
+            node = &mut node.children[i];
+            node.size -= size;
+            node.items -= items;
+        }
+        node.children.remove(idx);
+
+        // Viewing what was removed goes back to its parent, and the siblings after it moved.
+        let depth = parents.len();
+        let mut selected = self.window.selected();
+        if self.stack.starts_with(parents) {
+            match self.stack.get(depth).map(|viewed| viewed.cmp(&idx)) {
+                None if selected > idx => selected -= 1,
+                Some(cmp::Ordering::Equal) => {
+                    self.stack.truncate(depth);
+                    selected = idx;
+                }
+                Some(cmp::Ordering::Greater) => self.stack[depth] -= 1,
+                _ => {}
+            }
+        }
+        let len = self.node().map(|n| n.children.len()).unwrap_or(0);
+        self.window.reset(len);
+        self.window.select(selected);
+    }
+}
+
+fn scan(path: &path::Path, scanned: &mut u64, tx: &mpsc::Sender<ScanEvent>) -> io::Result<Node> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    *scanned += 1;
+    if scanned.is_multiple_of(PROGRESS_EVERY) {
+        let _ = tx.send(ScanEvent::Progress(*scanned));
+    }
+
+    let mut node = Node {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string_lossy().into_owned()),
+        path: path.to_path_buf(),
+        // Like `du`, the blocks taken and not the length, they differ for sparse and small files.
+        size: metadata.blocks() * 512,
+        items: 0,
+        is_dir: metadata.is_dir(),
+        children: Vec::new(),
+    };
+
+    if node.is_dir {
+        // Like `du`, what can't be read is skipped.
+        if let Ok(read_dir) = fs::read_dir(path) {
+            for entry in read_dir.flatten() {
+                if let Ok(child) = scan(&entry.path(), scanned, tx) {
+                    node.size += child.size;
+                    node.items += child.items + 1;
+                    node.children.push(child);
+                }
+            }
+        }
+        node.children.sort_by_key(|n| cmp::Reverse(n.size));
+    }
+
+    Ok(node)
+}
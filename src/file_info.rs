@@ -1,15 +1,124 @@
-use std::{fs, io, os::unix::fs::PermissionsExt, path, time};
+use std::{
+    fs, io,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path, time,
+};
 
 pub static INVALID_FILE: &str = "<INVALID>";
 
+/// What kind of file an entry is, as Unix sees it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileType {
+    /// Character used in the `ls -l` mode string.
+    pub fn mode_char(self) -> char {
+        match self {
+            FileType::Regular => '-',
+            FileType::Dir => 'd',
+            FileType::Symlink => 'l',
+            FileType::Fifo => 'p',
+            FileType::Socket => 's',
+            FileType::BlockDevice => 'b',
+            FileType::CharDevice => 'c',
+        }
+    }
+
+    /// Appended to the name to tell the type apart, like `ls -F`.
+    pub fn indicator(self) -> Option<char> {
+        match self {
+            FileType::Dir => Some('/'),
+            FileType::Fifo => Some('|'),
+            FileType::Socket => Some('='),
+            // Links already show where they point to.
+            FileType::Regular
+            | FileType::Symlink
+            | FileType::BlockDevice
+            | FileType::CharDevice => None,
+        }
+    }
+}
+
+impl From<fs::FileType> for FileType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            FileType::Dir
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_fifo() {
+            FileType::Fifo
+        } else if file_type.is_socket() {
+            FileType::Socket
+        } else if file_type.is_block_device() {
+            FileType::BlockDevice
+        } else if file_type.is_char_device() {
+            FileType::CharDevice
+        } else {
+            FileType::Regular
+        }
+    }
+}
+
+/// The timestamps Unix keeps for a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateKind {
+    /// Contents changed (mtime).
+    Modified,
+    /// Contents or metadata changed (ctime).
+    Changed,
+    /// Last read (atime).
+    Accessed,
+    /// Created, not every filesystem keeps it.
+    Birth,
+}
+
+impl DateKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            DateKind::Modified => "modified",
+            DateKind::Changed => "changed",
+            DateKind::Accessed => "accessed",
+            DateKind::Birth => "birth",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FileInfo {
     name: String,
     path_abs: path::PathBuf,
+    /// Type of the entry itself, links are not followed.
+    file_type: FileType,
+    /// For links, if what they point to is a directory.
     is_dir: bool,
+    /// Where the link points to, as written in the link.
+    link_target: Option<path::PathBuf>,
+    /// The link points to something that doesn't exist.
+    is_broken_link: bool,
     permissions: fs::Permissions,
     last_modified: time::SystemTime,
-    size_kib: u64,
+    last_changed: time::SystemTime,
+    last_accessed: time::SystemTime,
+    created: Option<time::SystemTime>,
+    size: u64,
+    uid: u32,
+    gid: u32,
+    inode: u64,
+    links: u64,
+    /// Amount of 512 bytes blocks allocated on disk.
+    blocks: u64,
+    /// Recursive size of the contents, only for directories once it was calculated.
+    dir_size: Option<u64>,
+    /// Why the entry couldn't be loaded, in that case everything but the name and path is made up.
+    error: Option<String>,
 }
 
 impl FileInfo {
@@ -25,10 +134,67 @@ impl FileInfo {
         self.permissions.mode()
     }
 
+    /// The type and permissions like `ls -l` shows them.
+    /// Eg.: `drwxr-xr-x`, `-rwsr-x--T`
+    pub fn mode_string(&self) -> String {
+        let mode = self.mode();
+        let mut mode_str = String::with_capacity(10);
+        mode_str.push(self.file_type.mode_char());
+
+        // Owner, group and others, each with the special bit that shares the execute column.
+        for (shift, special_bit, special_ch) in
+            [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')]
+        {
+            let bits = mode >> shift;
+            mode_str.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            mode_str.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            mode_str.push(match (mode & special_bit != 0, bits & 0o1 != 0) {
+                (true, true) => special_ch,
+                // Special bit set, but not executable.
+                (true, false) => special_ch.to_ascii_uppercase(),
+                (false, true) => 'x',
+                (false, false) => '-',
+            });
+        }
+
+        mode_str
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Regular file that anyone can execute.
+    pub fn is_executable(&self) -> bool {
+        self.file_type == FileType::Regular && self.mode() & 0o111 != 0
+    }
+
     pub fn is_dir(&self) -> bool {
         self.is_dir
     }
 
+    pub fn is_symlink(&self) -> bool {
+        self.file_type == FileType::Symlink
+    }
+
+    pub fn is_broken_link(&self) -> bool {
+        self.is_broken_link
+    }
+
+    pub fn link_target(&self) -> Option<&path::Path> {
+        self.link_target.as_deref()
+    }
+
+    /// Absolute path of what the link points to.
+    pub fn link_target_abs(&self) -> Option<path::PathBuf> {
+        let target = self.link_target()?;
+        // Relative targets are relative to the directory the link is in.
+        Some(match self.path_abs.parent() {
+            Some(parent) => parent.join(target),
+            None => target.to_path_buf(),
+        })
+    }
+
     pub fn is_read_only(&self) -> bool {
         self.permissions.readonly()
     }
@@ -37,52 +203,157 @@ impl FileInfo {
         &self.name
     }
 
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
     pub fn last_modified(&self) -> chrono::DateTime<chrono::Local> {
         // This is the only place where we use chrono.
         // Is this really needed?
         self.last_modified.into()
     }
 
-    /// Returns a pretty printed (with unit) size.
-    /// Eg.: 10KiB, 1.0MiB
-    pub fn pretty_size(&self) -> String {
-        if self.size_kib > 1024 * 1024 * 1024 * 1024 * 1024 {
-            format!(
-                "{s:3} PiB",
-                s = self.size_kib / 1024 * 1024 * 1024 * 1024 * 1024
-            )
-        } else if self.size_kib > 1024 * 1024 * 1024 * 1024 {
-            format!("{s:3} TiB", s = self.size_kib / 1024 * 1024 * 1024 * 1024)
-        } else if self.size_kib > 1024 * 1024 * 1024 {
-            format!("{s:3} GiB", s = self.size_kib / 1024 * 1024 * 1024)
-        } else if self.size_kib > 1024 * 1024 {
-            format!("{s:3} MiB", s = self.size_kib / 1024 * 1024)
-        } else if self.size_kib > 1024 {
-            format!("{s:3} KiB", s = self.size_kib / 1024)
-        } else {
-            format!("{s:3} B", s = self.size_kib)
-        }
+    /// `None` if the filesystem doesn't keep that date.
+    pub fn date(&self, kind: DateKind) -> Option<chrono::DateTime<chrono::Local>> {
+        let date = match kind {
+            DateKind::Modified => self.last_modified,
+            DateKind::Changed => self.last_changed,
+            DateKind::Accessed => self.last_accessed,
+            DateKind::Birth => self.created?,
+        };
+        Some(date.into())
+    }
+
+    /// Size in bytes, for directories the size of its contents if it was calculated.
+    pub fn size(&self) -> u64 {
+        self.dir_size.unwrap_or(self.size)
+    }
+
+    /// Space used on disk, which can be smaller (sparse files) or bigger (partially used blocks) than the size.
+    pub fn disk_size(&self) -> u64 {
+        self.blocks * 512
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    /// Amount of hard links.
+    pub fn links(&self) -> u64 {
+        self.links
+    }
+
+    pub fn set_dir_size(&mut self, size: u64) {
+        self.dir_size = Some(size);
     }
 }
 
-impl TryFrom<path::PathBuf> for FileInfo {
-    type Error = io::Error;
+impl FileInfo {
+    /// `metadata` must not follow links (`fs::symlink_metadata`),
+    /// so we can show the link itself instead of what it points to.
+    fn from_metadata(
+        path: path::PathBuf,
+        name: String,
+        metadata: fs::Metadata,
+    ) -> Result<Self, io::Error> {
+        let file_type = FileType::from(metadata.file_type());
+        let (is_dir, link_target, is_broken_link) = if file_type == FileType::Symlink {
+            // `fs::metadata` follows the link, if it fails there is nothing on the other side.
+            let target_metadata = fs::metadata(&path);
+            (
+                target_metadata.as_ref().is_ok_and(|m| m.is_dir()),
+                fs::read_link(&path).ok(),
+                target_metadata.is_err(),
+            )
+        } else {
+            (metadata.is_dir(), None, false)
+        };
 
-    fn try_from(path: path::PathBuf) -> Result<Self, Self::Error> {
-        let metadata = fs::metadata(&path)?;
         Ok(FileInfo {
-            is_dir: metadata.is_dir(),
+            name,
+            path_abs: path,
+            file_type,
+            is_dir,
+            link_target,
+            is_broken_link,
+            permissions: metadata.permissions(),
+            last_modified: metadata.modified()?, // TODO: Handle platforms where there is no modified time saved
+            last_changed: system_time(metadata.ctime(), metadata.ctime_nsec()),
+            last_accessed: metadata.accessed()?,
+            created: metadata.created().ok(),
+            size: metadata.len(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            inode: metadata.ino(),
+            links: metadata.nlink(),
+            blocks: metadata.blocks(),
+            dir_size: None,
+            error: None,
+        })
+    }
+
+    /// Placeholder for an entry that couldn't be loaded, so it can still be listed.
+    pub fn from_error(path: path::PathBuf, err: &io::Error) -> Self {
+        FileInfo {
             name: path
                 .file_name()
-                .unwrap_or_default() // TODO: Handle invalid files better
-                .to_str()
+                .and_then(|name| name.to_str())
                 .unwrap_or(INVALID_FILE)
                 .to_owned(),
-            permissions: metadata.permissions(),
             path_abs: path,
-            last_modified: metadata.modified()?, // TODO: Handle platforms where there is no modified time saved
-            size_kib: metadata.len(),
-        })
+            file_type: FileType::Regular,
+            is_dir: false,
+            link_target: None,
+            is_broken_link: false,
+            permissions: fs::Permissions::from_mode(0o000),
+            last_modified: time::UNIX_EPOCH,
+            last_changed: time::UNIX_EPOCH,
+            last_accessed: time::UNIX_EPOCH,
+            created: None,
+            size: 0,
+            uid: 0,
+            gid: 0,
+            inode: 0,
+            links: 0,
+            blocks: 0,
+            dir_size: None,
+            error: Some(err.to_string()),
+        }
+    }
+}
+
+/// From the seconds and nanoseconds since the epoch, as `stat` gives them.
+fn system_time(secs: i64, nsecs: i64) -> time::SystemTime {
+    let whole_secs = time::Duration::from_secs(secs.unsigned_abs());
+    let time = if secs >= 0 {
+        time::UNIX_EPOCH + whole_secs
+    } else {
+        time::UNIX_EPOCH - whole_secs
+    };
+    // The nanoseconds are always positive, even before the epoch.
+    time + time::Duration::from_nanos(nsecs.unsigned_abs())
+}
+
+impl TryFrom<path::PathBuf> for FileInfo {
+    type Error = io::Error;
+
+    fn try_from(path: path::PathBuf) -> Result<Self, Self::Error> {
+        let metadata = fs::symlink_metadata(&path)?;
+        let name = path
+            .file_name()
+            .unwrap_or_default() // TODO: Handle invalid files better
+            .to_str()
+            .unwrap_or(INVALID_FILE)
+            .to_owned();
+        FileInfo::from_metadata(path, name, metadata)
     }
 }
 
@@ -90,18 +361,73 @@ impl TryFrom<fs::DirEntry> for FileInfo {
     type Error = io::Error;
 
     fn try_from(value: fs::DirEntry) -> Result<Self, Self::Error> {
+        // Doesn't follow links.
         let metadata = value.metadata()?;
-        Ok(FileInfo {
-            name: value
-                .file_name()
-                .to_str()
-                .unwrap_or(INVALID_FILE)
-                .to_owned(),
-            path_abs: value.path(),
-            is_dir: metadata.is_dir(),
-            permissions: metadata.permissions(),
-            last_modified: metadata.modified()?,
-            size_kib: metadata.len(),
-        })
+        let name = value
+            .file_name()
+            .to_str()
+            .unwrap_or(INVALID_FILE)
+            .to_owned();
+        FileInfo::from_metadata(value.path(), name, metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_mode(file_type: FileType, mode: u32) -> FileInfo {
+        let mut info = FileInfo::from_error("/test".into(), &io::Error::other("test"));
+        info.file_type = file_type;
+        info.permissions = fs::Permissions::from_mode(mode);
+        info
+    }
+
+    #[test]
+    fn mode_string_shows_type_and_permissions() {
+        assert_eq!(with_mode(FileType::Dir, 0o755).mode_string(), "drwxr-xr-x");
+        assert_eq!(
+            with_mode(FileType::Regular, 0o644).mode_string(),
+            "-rw-r--r--"
+        );
+        assert_eq!(
+            with_mode(FileType::Symlink, 0o777).mode_string(),
+            "lrwxrwxrwx"
+        );
+        assert_eq!(with_mode(FileType::Fifo, 0o600).mode_string(), "prw-------");
+        assert_eq!(
+            with_mode(FileType::Socket, 0o000).mode_string(),
+            "s---------"
+        );
+        assert_eq!(
+            with_mode(FileType::BlockDevice, 0o660).mode_string(),
+            "brw-rw----"
+        );
+        assert_eq!(
+            with_mode(FileType::CharDevice, 0o666).mode_string(),
+            "crw-rw-rw-"
+        );
+    }
+
+    #[test]
+    fn mode_string_shows_special_bits() {
+        assert_eq!(
+            with_mode(FileType::Regular, 0o4755).mode_string(),
+            "-rwsr-xr-x"
+        );
+        assert_eq!(
+            with_mode(FileType::Regular, 0o4644).mode_string(),
+            "-rwSr--r--"
+        );
+        assert_eq!(
+            with_mode(FileType::Regular, 0o2755).mode_string(),
+            "-rwxr-sr-x"
+        );
+        assert_eq!(
+            with_mode(FileType::Regular, 0o2745).mode_string(),
+            "-rwxr-Sr-x"
+        );
+        assert_eq!(with_mode(FileType::Dir, 0o1777).mode_string(), "drwxrwxrwt");
+        assert_eq!(with_mode(FileType::Dir, 0o1776).mode_string(), "drwxrwxrwT");
     }
 }
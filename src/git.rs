@@ -0,0 +1,377 @@
+use std::{collections::HashMap, path, process, sync::mpsc, thread};
+
+/// Git status of an entry, from the most to the least important.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileStatus {
+    /// Merge conflict.
+    Conflicted,
+    /// Changed in the working tree, staged or not.
+    Modified,
+    /// Changed in the index only.
+    Staged,
+    Untracked,
+    Ignored,
+}
+
+impl FileStatus {
+    pub fn symbol(self) -> char {
+        match self {
+            FileStatus::Conflicted => 'U',
+            FileStatus::Modified => 'M',
+            FileStatus::Staged => '+',
+            FileStatus::Untracked => '?',
+            FileStatus::Ignored => '!',
+        }
+    }
+}
+
+/// Status of the repository the current directory is in.
+#[derive(Debug, Default)]
+pub struct RepoStatus {
+    pub root: path::PathBuf,
+    /// `None` when detached.
+    pub branch: Option<String>,
+    /// Commits ahead and behind the upstream, if there is one.
+    pub ahead_behind: Option<(u64, u64)>,
+    files: HashMap<path::PathBuf, FileStatus>,
+    /// Most important status of what is inside of each directory.
+    dirs: HashMap<path::PathBuf, FileStatus>,
+}
+
+impl RepoStatus {
+    /// Status of `path`, for directories the most important of their contents.
+    pub fn get(&self, path: &path::Path) -> Option<FileStatus> {
+        if let Some(status) = self.files.get(path).or_else(|| self.dirs.get(path)) {
+            return Some(*status);
+        }
+
+        // Everything inside of untracked or ignored directories is too.
+        path.ancestors()
+            .skip(1)
+            .take_while(|p| p.starts_with(&self.root) && *p != self.root)
+            .find_map(|p| match self.files.get(p) {
+                Some(status @ (FileStatus::Untracked | FileStatus::Ignored)) => Some(*status),
+                _ => None,
+            })
+    }
+
+    /// Parses the output of `git status --porcelain=v2 --branch -z`.
+    fn parse(root: path::PathBuf, output: &str) -> Self {
+        let mut status = Self {
+            root,
+            ..Self::default()
+        };
+
+        let mut records = output.split('\0');
+        while let Some(record) = records.next() {
+            let (kind, rest) = record.split_once(' ').unwrap_or((record, ""));
+            let (file_status, path) = match kind {
+                "#" => {
+                    status.parse_header(rest);
+                    continue;
+                }
+                "1" => (xy_status(rest), rest.splitn(8, ' ').nth(7)),
+                "2" => {
+                    // The original path of renames comes as the next record.
+                    records.next();
+                    (xy_status(rest), rest.splitn(9, ' ').nth(8))
+                }
+                "u" => (Some(FileStatus::Conflicted), rest.splitn(10, ' ').nth(9)),
+                "?" => (Some(FileStatus::Untracked), Some(rest)),
+                "!" => (Some(FileStatus::Ignored), Some(rest)),
+                _ => continue,
+            };
+            let (Some(file_status), Some(path)) = (file_status, path) else {
+                continue;
+            };
+
+            // Untracked and ignored directories end with `/`.
+            let path = status.root.join(path.trim_end_matches('/'));
+            if file_status != FileStatus::Ignored {
+                for dir in path.ancestors().skip(1) {
+                    if !dir.starts_with(&status.root) {
+                        break;
+                    }
+                    let dir_status = status.dirs.entry(dir.to_path_buf()).or_insert(file_status);
+                    *dir_status = (*dir_status).min(file_status);
+                }
+            }
+            status.files.insert(path, file_status);
+        }
+
+        status
+    }
+
+    fn parse_header(&mut self, header: &str) {
+        let Some((key, value)) = header.split_once(' ') else {
+            return;
+        };
+        match key {
+            "branch.head" if value != "(detached)" => self.branch = Some(value.to_owned()),
+            "branch.ab" => {
+                let mut counts = value
+                    .split(' ')
+                    .map(|n| n.trim_start_matches(['+', '-']).parse().unwrap_or(0));
+                self.ahead_behind = Some((counts.next().unwrap_or(0), counts.next().unwrap_or(0)));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Status from the `XY` field, `X` being the index and `Y` the working tree.
+fn xy_status(fields: &str) -> Option<FileStatus> {
+    let mut xy = fields.chars();
+    match (xy.next()?, xy.next()?) {
+        (_, 'M' | 'D' | 'T') => Some(FileStatus::Modified),
+        ('.', _) => None,
+        _ => Some(FileStatus::Staged),
+    }
+}
+
+/// Runs `git` in `dir` with `args`, followed by `--` and `paths` if there are any, and returns its output.
+pub fn git(dir: &path::Path, args: &[&str], paths: &[&path::Path]) -> Result<String, String> {
+    run(dir, args, paths, false)
+}
+
+/// `allow_differences` is for commands like `diff --no-index`, that exit with 1 when there are differences.
+fn run(
+    dir: &path::Path,
+    args: &[&str],
+    paths: &[&path::Path],
+    allow_differences: bool,
+) -> Result<String, String> {
+    let mut command = process::Command::new("git");
+    command
+        .args(args)
+        .current_dir(dir)
+        .stdin(process::Stdio::null());
+    if !paths.is_empty() {
+        command.arg("--").args(paths);
+    }
+
+    let output = command
+        .output()
+        .map_err(|e| format!("could not run git: {e}"))?;
+    let differences = allow_differences && output.status.code() == Some(1);
+    if !output.status.success() && !differences {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Removes the changes of `paths` from the index, leaving the working tree as it is.
+pub fn unstage(dir: &path::Path, paths: &[&path::Path]) -> Result<String, String> {
+    if git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"], &[]).is_ok() {
+        git(dir, &["restore", "--staged"], paths)
+    } else {
+        // Without commits there is nothing to restore from, so they are just removed from the index.
+        git(dir, &["rm", "--cached", "-r", "--quiet"], paths)
+    }
+}
+
+/// Changes of `path` against the last commit, staged or not.
+/// Untracked files are shown as entirely added, and so are the files inside of untracked directories.
+fn diff(dir: &path::Path, path: &path::Path, untracked: bool) -> Result<String, String> {
+    if untracked && path.is_dir() {
+        let files = git(
+            dir,
+            &["ls-files", "--others", "--exclude-standard", "-z"],
+            &[path],
+        )?;
+        let mut diffs = String::new();
+        for file in files.split('\0').filter(|f| !f.is_empty()) {
+            diffs.push_str(&diff(dir, &dir.join(file), true)?);
+        }
+        return Ok(diffs);
+    }
+    if untracked {
+        let paths = [path::Path::new("/dev/null"), path];
+        return run(dir, &["diff", "--no-color", "--no-index"], &paths, true);
+    }
+
+    git(dir, &["diff", "--no-color", "HEAD"], &[path])
+        // Without commits there is no `HEAD` yet, only the index to compare against.
+        .or_else(|_| git(dir, &["diff", "--no-color", "--cached"], &[path]))
+}
+
+/// Finds the repository `dir` is in and reads its status.
+/// `Ok(None)` if it's not inside a repository.
+fn read_status(dir: &path::Path) -> Result<Option<RepoStatus>, String> {
+    let Ok(root) = git(dir, &["rev-parse", "--show-toplevel"], &[]) else {
+        return Ok(None);
+    };
+    let root = path::PathBuf::from(root.trim_end_matches('\n'));
+
+    let output = git(
+        dir,
+        &["status", "--porcelain=v2", "--branch", "-z", "--ignored"],
+        &[],
+    )?;
+    Ok(Some(RepoStatus::parse(root, &output)))
+}
+
+type StatusResult = Result<Option<RepoStatus>, String>;
+
+/// Reads the git status of the current directory in the background.
+/// Only local, it never fetches.
+pub struct GitStatus {
+    tx: mpsc::Sender<(u64, StatusResult)>,
+    rx: mpsc::Receiver<(u64, StatusResult)>,
+    /// Identifies the last request, so results of older ones are dropped.
+    generation: u64,
+    pub repo: Option<RepoStatus>,
+}
+
+impl GitStatus {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx,
+            generation: 0,
+            repo: None,
+        }
+    }
+
+    /// Starts reading the status of `dir`, the previous one is kept until it's done.
+    pub fn request(&mut self, dir: path::PathBuf) {
+        self.generation += 1;
+        let generation = self.generation;
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let _ = tx.send((generation, read_status(&dir)));
+        });
+    }
+
+    /// Checks on the last request.
+    /// Returns its result once it arrives.
+    pub fn poll(&mut self) -> Option<Result<(), String>> {
+        let mut result = None;
+        while let Ok((generation, status)) = self.rx.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+            result = Some(status.map(|repo| self.repo = repo));
+        }
+        result
+    }
+}
+
+type DiffResult = Result<String, String>;
+
+/// Reads the changes of a file in the background.
+pub struct GitDiff {
+    tx: mpsc::Sender<(u64, path::PathBuf, DiffResult)>,
+    rx: mpsc::Receiver<(u64, path::PathBuf, DiffResult)>,
+    /// Identifies the last request, so results of older ones are dropped.
+    generation: u64,
+}
+
+impl GitDiff {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx,
+            generation: 0,
+        }
+    }
+
+    /// Starts reading the changes of `path`, in the repository at `root`.
+    /// See `diff`.
+    pub fn request(&mut self, root: path::PathBuf, path: path::PathBuf, untracked: bool) {
+        self.generation += 1;
+        let generation = self.generation;
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let diff = diff(&root, &path, untracked);
+            let _ = tx.send((generation, path, diff));
+        });
+    }
+
+    /// Checks on the last request.
+    /// Returns the path and its changes once they arrive.
+    pub fn poll(&mut self) -> Option<(path::PathBuf, DiffResult)> {
+        let mut result = None;
+        while let Ok((generation, path, diff)) = self.rx.try_recv() {
+            if generation == self.generation {
+                result = Some((path, diff));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(records: &[&str]) -> RepoStatus {
+        let mut output = records.join("\0");
+        output.push('\0');
+        RepoStatus::parse("/repo".into(), &output)
+    }
+
+    fn get(status: &RepoStatus, path: &str) -> Option<FileStatus> {
+        status.get(&path::Path::new("/repo").join(path))
+    }
+
+    #[test]
+    fn parse_reads_the_branch() {
+        let status = parse(&[
+            "# branch.oid 1234",
+            "# branch.head main",
+            "# branch.upstream origin/main",
+            "# branch.ab +2 -1",
+        ]);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.ahead_behind, Some((2, 1)));
+
+        let status = parse(&["# branch.oid 1234", "# branch.head (detached)"]);
+        assert_eq!(status.branch, None);
+        assert_eq!(status.ahead_behind, None);
+    }
+
+    #[test]
+    fn parse_reads_the_files() {
+        let status = parse(&[
+            "1 .M N... 100644 100644 100644 1234 1234 src/main.rs",
+            "1 A. N... 000000 100644 100644 0000 1234 src/new file.rs",
+            "1 MM N... 100644 100644 100644 1234 1234 both.rs",
+            "2 R. N... 100644 100644 100644 1234 1234 R100 renamed.rs",
+            "old.rs",
+            "u UU N... 100644 100644 100644 100644 1234 1234 1234 conflict.rs",
+            "? notes.txt",
+            "! target/",
+        ]);
+        assert_eq!(get(&status, "src/main.rs"), Some(FileStatus::Modified));
+        assert_eq!(get(&status, "src/new file.rs"), Some(FileStatus::Staged));
+        assert_eq!(get(&status, "both.rs"), Some(FileStatus::Modified));
+        assert_eq!(get(&status, "renamed.rs"), Some(FileStatus::Staged));
+        assert_eq!(get(&status, "old.rs"), None);
+        assert_eq!(get(&status, "conflict.rs"), Some(FileStatus::Conflicted));
+        assert_eq!(get(&status, "notes.txt"), Some(FileStatus::Untracked));
+        assert_eq!(get(&status, "target"), Some(FileStatus::Ignored));
+        assert_eq!(get(&status, "clean.rs"), None);
+    }
+
+    #[test]
+    fn directories_get_the_most_important_status_inside() {
+        let status = parse(&[
+            "1 A. N... 000000 100644 100644 0000 1234 src/a/new.rs",
+            "1 .M N... 100644 100644 100644 1234 1234 src/b.rs",
+            "? src/a/notes.txt",
+            "! src/a/build/",
+        ]);
+        assert_eq!(get(&status, "src"), Some(FileStatus::Modified));
+        assert_eq!(get(&status, "src/a"), Some(FileStatus::Staged));
+    }
+
+    #[test]
+    fn entries_inside_untracked_and_ignored_directories_are_too() {
+        let status = parse(&["? new/", "! target/"]);
+        assert_eq!(get(&status, "new/sub/file"), Some(FileStatus::Untracked));
+        assert_eq!(get(&status, "target/debug"), Some(FileStatus::Ignored));
+    }
+}
@@ -0,0 +1,196 @@
+use std::{env, fs, io, path};
+
+/// Lines run from command mode, kept in `$XDG_STATE_HOME/dune/history` (`~/.local/state/dune/history` by default).
+/// One line per command, oldest first, without duplicates.
+#[derive(Debug, Default)]
+pub struct History {
+    /// `None` if it's not saved, because there is no home or the file couldn't be read.
+    path: Option<path::PathBuf>,
+    /// How many lines are kept, the oldest ones are forgotten first.
+    limit: usize,
+    /// Oldest first.
+    lines: Vec<String>,
+    /// Line shown while going through the history, and what was typed before starting.
+    nav: Option<(usize, String)>,
+}
+
+impl History {
+    pub fn path() -> Option<path::PathBuf> {
+        let state_home = env::var_os("XDG_STATE_HOME")
+            .map(path::PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| {
+                env::var_os("HOME").map(|home| path::Path::new(&home).join(".local").join("state"))
+            })?;
+        Some(state_home.join("dune").join("history"))
+    }
+
+    /// A history that is not saved.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    /// Reads the history file.
+    /// Not having one is the same as having an empty one.
+    pub fn load(limit: usize) -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::new(limit));
+        };
+
+        let lines = match fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_owned).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("{path}: {e}", path = path.to_string_lossy())),
+        };
+        let mut history = Self {
+            path: Some(path),
+            limit,
+            lines,
+            nav: None,
+        };
+        history.forget_oldest();
+        Ok(history)
+    }
+
+    /// Adds `line` as the newest one, and saves the history.
+    /// If it was already there it's moved, instead of repeated.
+    pub fn push(&mut self, line: &str) -> Result<(), String> {
+        self.nav = None;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        self.lines.retain(|l| l != line);
+        self.lines.push(line.to_owned());
+        self.forget_oldest();
+        self.save()
+    }
+
+    fn forget_oldest(&mut self) {
+        let extra = self.lines.len().saturating_sub(self.limit);
+        self.lines.drain(..extra);
+    }
+
+    /// Writes the whole history, replacing the file at once so it's never half written.
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        let tmp_path = path.with_extension("tmp");
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&tmp_path, text))
+            .and_then(|()| fs::rename(&tmp_path, path));
+        result.map_err(|e| format!("{path}: {e}", path = path.to_string_lossy()))
+    }
+
+    /// The line before the one shown, `typed` is what was in the prompt before going through the history.
+    /// `None` if there are no older lines.
+    pub fn prev(&mut self, typed: &str) -> Option<&str> {
+        let idx = match &self.nav {
+            Some((0, _)) => return None,
+            Some((idx, _)) => idx - 1,
+            None => self.lines.len().checked_sub(1)?,
+        };
+        let typed = self.nav.take().map_or_else(|| typed.to_owned(), |(_, t)| t);
+        self.nav = Some((idx, typed));
+        self.lines.get(idx).map(String::as_str)
+    }
+
+    /// The line after the one shown, or what was typed when going past the newest.
+    /// `None` if not going through the history.
+    pub fn next(&mut self) -> Option<String> {
+        let (idx, typed) = self.nav.take()?;
+        if idx + 1 < self.lines.len() {
+            self.nav = Some((idx + 1, typed));
+            self.lines.get(idx + 1).cloned()
+        } else {
+            Some(typed)
+        }
+    }
+
+    /// Stops going through the history.
+    pub fn reset(&mut self) {
+        self.nav = None;
+    }
+
+    /// Newest line containing `query`, older than line `before`.
+    pub fn search(&self, query: &str, before: usize) -> Option<(usize, &str)> {
+        self.lines[..before.min(self.lines.len())]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, line)| line.contains(query))
+            .map(|(idx, line)| (idx, line.as_str()))
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.lines.get(idx).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(lines: &[&str]) -> History {
+        let mut history = History::new(10);
+        for line in lines {
+            history.push(line).unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn push_moves_repeated_lines_and_forgets_the_oldest() {
+        let mut history = History::new(3);
+        for line in ["a", "b", "a", " ", "c", "d"] {
+            history.push(line).unwrap();
+        }
+        assert_eq!(history.lines, ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn prev_and_next_go_through_the_lines() {
+        let mut history = history(&["a", "b", "c"]);
+        assert_eq!(history.prev("typed"), Some("c"));
+        assert_eq!(history.prev("ignored"), Some("b"));
+        assert_eq!(history.prev(""), Some("a"));
+        assert_eq!(history.prev(""), None);
+        assert_eq!(history.next().as_deref(), Some("b"));
+        assert_eq!(history.next().as_deref(), Some("c"));
+        // Past the newest is what was typed before starting.
+        assert_eq!(history.next().as_deref(), Some("typed"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn prev_starts_again_after_reset() {
+        let mut history = history(&["a", "b"]);
+        history.prev("");
+        history.prev("");
+        history.reset();
+        assert_eq!(history.prev(""), Some("b"));
+        assert_eq!(History::new(10).prev(""), None);
+    }
+
+    #[test]
+    fn search_finds_the_newest_older_match() {
+        let history = history(&["git status", "ls", "git log", "cargo build"]);
+        assert_eq!(history.search("git", history.len()), Some((2, "git log")));
+        assert_eq!(history.search("git", 2), Some((0, "git status")));
+        assert_eq!(history.search("git", 0), None);
+        assert_eq!(history.search("make", history.len()), None);
+        assert_eq!(history.search("", 100), Some((3, "cargo build")));
+    }
+}
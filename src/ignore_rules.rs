@@ -0,0 +1,82 @@
+use std::path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Files with patterns of entries to ignore, from the most to the least specific inside a directory.
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// The ignore rules that apply to the entries of a directory:
+/// `.ignore` and `.gitignore` of it and its parents up to the root of the repository,
+/// `.git/info/exclude` and the global git excludes (`core.excludesFile`).
+pub struct IgnoreRules {
+    /// Most specific first, so the first one that matches decides.
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Reads the rules for `dir`.
+    /// Files that can't be read or have invalid patterns are returned as errors, the rest still apply.
+    pub fn for_dir(dir: &path::Path) -> (Self, Vec<String>) {
+        let mut matchers = Vec::new();
+        let mut errors = Vec::new();
+        let mut add = |(matcher, err): (Gitignore, Option<ignore::Error>)| {
+            if let Some(err) = err {
+                errors.push(err.to_string());
+            }
+            if !matcher.is_empty() {
+                matchers.push(matcher);
+            }
+        };
+
+        // Where the patterns of the global excludes start from: the repository, or else `dir`.
+        let mut root = dir;
+        for ancestor in dir.ancestors() {
+            for file in IGNORE_FILES {
+                let path = ancestor.join(file);
+                if path.is_file() {
+                    add(Gitignore::new(path));
+                }
+            }
+
+            // Rules of the parents of a repository don't apply inside of it.
+            let git_dir = ancestor.join(".git");
+            if git_dir.exists() {
+                let exclude = git_dir.join("info").join("exclude");
+                if exclude.is_file() {
+                    // Its patterns are relative to the repository, not to where the file is.
+                    let mut builder = GitignoreBuilder::new(ancestor);
+                    let err = builder.add(exclude);
+                    match builder.build() {
+                        Ok(matcher) => add((matcher, err)),
+                        Err(e) => add((Gitignore::empty(), Some(e))),
+                    }
+                }
+                root = ancestor;
+                break;
+            }
+        }
+        add(GitignoreBuilder::new(root).build_global());
+
+        (Self { matchers }, errors)
+    }
+
+    /// If `path` or one of its parents is ignored.
+    /// `path` is expected to be an entry of the directory the rules are for.
+    pub fn is_ignored(&self, path: &path::Path, is_dir: bool) -> bool {
+        for matcher in &self.matchers {
+            // It panics with paths that are not inside of where the patterns start from.
+            let matched = if path.starts_with(matcher.path()) {
+                matcher.matched_path_or_any_parents(path, is_dir)
+            } else {
+                matcher.matched(path, is_dir)
+            };
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,181 @@
+use std::{cell::Cell, panic, sync::mpsc, thread};
+
+pub type JobId = usize;
+
+/// Messages sent from the job thread back to the UI.
+enum JobEvent {
+    Progress { id: JobId, done: u64, total: u64 },
+    Finished { id: JobId, result: JobResult },
+}
+
+/// What a job reports when it's done.
+/// `Ok` and `Err` hold a message to be shown to the user.
+pub type JobResult = Result<String, String>;
+
+/// Handle given to the job so it can report how much work it has done.
+pub struct Progress {
+    id: JobId,
+    tx: mpsc::Sender<JobEvent>,
+    last_percent: Cell<u64>,
+}
+
+impl Progress {
+    /// Reports progress back to the UI.
+    /// Only sends when the percentage changes, so it's fine to call it in tight loops.
+    pub fn report(&self, done: u64, total: u64) {
+        let percent = percent(done, total);
+        if percent == self.last_percent.get() {
+            return;
+        }
+        self.last_percent.set(percent);
+
+        // If the UI is gone there is no one to report to, so ignore the error.
+        let _ = self.tx.send(JobEvent::Progress {
+            id: self.id,
+            done,
+            total,
+        });
+    }
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub name: String,
+    pub done: u64,
+    pub total: u64,
+}
+
+impl Job {
+    pub fn percent(&self) -> u64 {
+        percent(self.done, self.total)
+    }
+}
+
+/// Runs work in background threads and collects their progress.
+pub struct Jobs {
+    tx: mpsc::Sender<JobEvent>,
+    rx: mpsc::Receiver<JobEvent>,
+    running: Vec<Job>,
+    next_id: JobId,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx,
+            running: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Runs `work` in a new thread.
+    /// If it panics it fails, instead of running forever.
+    pub fn spawn<F>(&mut self, name: String, work: F) -> JobId
+    where
+        F: FnOnce(&Progress) -> JobResult + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let progress = Progress {
+            id,
+            tx: self.tx.clone(),
+            last_percent: Cell::new(0),
+        };
+        thread::spawn(move || {
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| work(&progress)))
+                .unwrap_or_else(|payload| {
+                    let reason = payload
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or("unknown reason");
+                    Err(format!("it crashed: {reason}"))
+                });
+            let _ = progress.tx.send(JobEvent::Finished { id, result });
+        });
+
+        self.running.push(Job {
+            id,
+            name,
+            done: 0,
+            total: 0,
+        });
+
+        id
+    }
+
+    pub fn running(&self) -> &[Job] {
+        &self.running
+    }
+
+    /// Updates the progress of the running jobs.
+    /// Returns the jobs that finished since the last poll.
+    pub fn poll(&mut self) -> Vec<(Job, JobResult)> {
+        let mut finished = Vec::new();
+        while let Ok(evt) = self.rx.try_recv() {
+            match evt {
+                JobEvent::Progress { id, done, total } => {
+                    if let Some(job) = self.running.iter_mut().find(|j| j.id == id) {
+                        job.done = done;
+                        job.total = total;
+                    }
+                }
+                JobEvent::Finished { id, result } => {
+                    if let Some(idx) = self.running.iter().position(|j| j.id == id) {
+                        finished.push((self.running.remove(idx), result));
+                    }
+                }
+            }
+        }
+        finished
+    }
+}
+
+fn percent(done: u64, total: u64) -> u64 {
+    done.saturating_mul(100).checked_div(total).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time;
+
+    use super::*;
+
+    /// Polls until a job finishes.
+    fn wait(jobs: &mut Jobs) -> (Job, JobResult) {
+        let start = time::Instant::now();
+        loop {
+            if let Some(finished) = jobs.poll().pop() {
+                return finished;
+            }
+            assert!(
+                start.elapsed() < time::Duration::from_secs(5),
+                "job never finished"
+            );
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn finished_jobs_are_returned() {
+        let mut jobs = Jobs::new();
+        let id = jobs.spawn("ok".into(), |_| Ok("done".into()));
+        let (job, result) = wait(&mut jobs);
+        assert_eq!(job.id, id);
+        assert_eq!(result, Ok("done".into()));
+        assert!(jobs.running().is_empty());
+    }
+
+    #[test]
+    fn jobs_that_panic_fail() {
+        let mut jobs = Jobs::new();
+        jobs.spawn("panics".into(), |_| panic!("oops"));
+        let (_, result) = wait(&mut jobs);
+        assert_eq!(result, Err("it crashed: oops".into()));
+        assert!(jobs.running().is_empty());
+    }
+}
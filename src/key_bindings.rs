@@ -12,12 +12,79 @@ pub enum ActionExplorer {
     DirLeave,
     EntriesUpdate,
     EntriesSortToggle,
+    SortReverseToggle,
+    SortCaseToggle,
+    SortDirsFirstToggle,
+    SortHiddenLastToggle,
+    EntryMarkToggle,
+    DirSizesCalculate,
+    DirSizesToggleAuto,
+    DiskUsageOpen,
+    LinkFollow,
+    ColumnsEdit,
+    DetailsToggle,
+    GitStage,
+    GitUnstage,
+    GitDiscard,
+    GitDiff,
+    OutputOpen,
+    CommandsList,
+    TerminalFocus,
+    TerminalHide,
+    HiddenToggle,
+    IgnoredToggle,
+    ArchiveCreate,
+    ArchiveExtract,
+    ArchiveExtractToDir,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum ActionCommand {
     Execute,
     PromptBackspace,
+    HistoryPrev,
+    HistoryNext,
+    /// Finds older lines of the history as they are typed.
+    HistorySearch,
+    /// Completes the last word, it takes Tab from the global bindings once something is typed.
+    Complete,
+    Leave,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ActionDiskUsage {
+    NavLineUp,
+    NavLineDown,
+    NavHome,
+    NavEnd,
+    DirEnter,
+    DirLeave,
+    EntryDelete,
+    Rescan,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ActionPager {
+    NavLineUp,
+    NavLineDown,
+    NavPageUp,
+    NavPageDown,
+    NavHome,
+    NavEnd,
+    SearchOpen,
+    SearchNext,
+    SearchPrev,
+    /// Older command output.
+    OutputPrev,
+    /// Newer command output.
+    OutputNext,
+    Close,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ActionTerminal {
+    /// Back to the explorer, the terminal stays open.
+    Unfocus,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -30,12 +97,19 @@ pub enum ActionGlobal {
 pub enum Action {
     Explorer(ActionExplorer),
     Command(ActionCommand),
+    DiskUsage(ActionDiskUsage),
+    Pager(ActionPager),
+    Terminal(ActionTerminal),
     Global(ActionGlobal),
 }
 
 pub struct KeyBindings {
     explorer: HashMap<Event, Action>,
     command: HashMap<Event, Action>,
+    disk_usage: HashMap<Event, Action>,
+    pager: HashMap<Event, Action>,
+    /// The only keys not sent to the terminal while it's focused, not even the global ones.
+    terminal: HashMap<Event, Action>,
     global: HashMap<Event, Action>,
 }
 
@@ -56,6 +130,30 @@ impl KeyBindings {
         }
     }
 
+    pub fn get_disk_usage(&mut self, event: &Event) -> Option<&ActionDiskUsage> {
+        if let Some(Action::DiskUsage(action)) = self.disk_usage.get(event) {
+            Some(action)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_pager(&mut self, event: &Event) -> Option<&ActionPager> {
+        if let Some(Action::Pager(action)) = self.pager.get(event) {
+            Some(action)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_terminal(&mut self, event: &Event) -> Option<&ActionTerminal> {
+        if let Some(Action::Terminal(action)) = self.terminal.get(event) {
+            Some(action)
+        } else {
+            None
+        }
+    }
+
     pub fn get_global(&mut self, event: &Event) -> Option<&ActionGlobal> {
         if let Some(Action::Global(action)) = self.global.get(event) {
             Some(action)
@@ -74,6 +172,15 @@ pub fn from_key_code(code: KeyCode) -> Event {
     })
 }
 
+pub fn from_key_code_with_modifiers(code: KeyCode, modifiers: KeyModifiers) -> Event {
+    Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind: KeyEventKind::Press,
+        state: KeyEventState::NONE,
+    })
+}
+
 pub fn new() -> KeyBindings {
     // TODO: take the key bindings from a file and parse it
     KeyBindings {
@@ -109,7 +216,103 @@ pub fn new() -> KeyBindings {
             ( 
                 from_key_code(KeyCode::F(3)),
                 Action::Explorer(ActionExplorer::EntriesSortToggle)
-            )
+            ),
+            (
+                from_key_code(KeyCode::Char(' ')),
+                Action::Explorer(ActionExplorer::EntryMarkToggle),
+            ),
+            (
+                from_key_code(KeyCode::Char('s')),
+                Action::Explorer(ActionExplorer::DirSizesCalculate),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('S'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::DirSizesToggleAuto),
+            ),
+            (
+                from_key_code(KeyCode::Char('u')),
+                Action::Explorer(ActionExplorer::DiskUsageOpen),
+            ),
+            (
+                from_key_code(KeyCode::Char('g')),
+                Action::Explorer(ActionExplorer::LinkFollow),
+            ),
+            (
+                from_key_code(KeyCode::Char('c')),
+                Action::Explorer(ActionExplorer::ColumnsEdit),
+            ),
+            (
+                from_key_code(KeyCode::Char('z')),
+                Action::Explorer(ActionExplorer::ArchiveCreate),
+            ),
+            (
+                from_key_code(KeyCode::Char('x')),
+                Action::Explorer(ActionExplorer::ArchiveExtract),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('X'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::ArchiveExtractToDir),
+            ),
+            (
+                from_key_code(KeyCode::Char('i')),
+                Action::Explorer(ActionExplorer::DetailsToggle),
+            ),
+            (
+                from_key_code(KeyCode::Char('.')),
+                Action::Explorer(ActionExplorer::HiddenToggle),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('I'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::IgnoredToggle),
+            ),
+            (
+                from_key_code(KeyCode::Char('a')),
+                Action::Explorer(ActionExplorer::GitStage),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('A'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::GitUnstage),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('R'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::GitDiscard),
+            ),
+            (
+                from_key_code(KeyCode::Char('v')),
+                Action::Explorer(ActionExplorer::GitDiff),
+            ),
+            (
+                from_key_code(KeyCode::Char('o')),
+                Action::Explorer(ActionExplorer::OutputOpen),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('J'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::CommandsList),
+            ),
+            (
+                from_key_code(KeyCode::Char('`')),
+                Action::Explorer(ActionExplorer::TerminalFocus),
+            ),
+            (
+                from_key_code(KeyCode::Char('~')),
+                Action::Explorer(ActionExplorer::TerminalHide),
+            ),
+            (
+                from_key_code(KeyCode::Char('r')),
+                Action::Explorer(ActionExplorer::SortReverseToggle),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('C'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::SortCaseToggle),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('D'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::SortDirsFirstToggle),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('H'), KeyModifiers::SHIFT),
+                Action::Explorer(ActionExplorer::SortHiddenLastToggle),
+            ),
         ]),
         command: HashMap::from([
             (
@@ -120,7 +323,123 @@ pub fn new() -> KeyBindings {
                 from_key_code(KeyCode::Backspace),
                 Action::Command(ActionCommand::PromptBackspace),
             ),
+            (
+                from_key_code(KeyCode::Up),
+                Action::Command(ActionCommand::HistoryPrev),
+            ),
+            (
+                from_key_code(KeyCode::Down),
+                Action::Command(ActionCommand::HistoryNext),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('r'), KeyModifiers::CONTROL),
+                Action::Command(ActionCommand::HistorySearch),
+            ),
+            (
+                from_key_code(KeyCode::Tab),
+                Action::Command(ActionCommand::Complete),
+            ),
+            (
+                from_key_code(KeyCode::Esc),
+                Action::Command(ActionCommand::Leave),
+            ),
+        ]),
+        disk_usage: HashMap::from([
+            (
+                from_key_code(KeyCode::Up),
+                Action::DiskUsage(ActionDiskUsage::NavLineUp),
+            ),
+            (
+                from_key_code(KeyCode::Down),
+                Action::DiskUsage(ActionDiskUsage::NavLineDown),
+            ),
+            (
+                from_key_code(KeyCode::Home),
+                Action::DiskUsage(ActionDiskUsage::NavHome),
+            ),
+            (
+                from_key_code(KeyCode::End),
+                Action::DiskUsage(ActionDiskUsage::NavEnd),
+            ),
+            (
+                from_key_code(KeyCode::Enter),
+                Action::DiskUsage(ActionDiskUsage::DirEnter),
+            ),
+            (
+                from_key_code(KeyCode::Backspace),
+                Action::DiskUsage(ActionDiskUsage::DirLeave),
+            ),
+            (
+                from_key_code(KeyCode::Char('d')),
+                Action::DiskUsage(ActionDiskUsage::EntryDelete),
+            ),
+            (
+                from_key_code(KeyCode::F(5)),
+                Action::DiskUsage(ActionDiskUsage::Rescan),
+            ),
+        ]),
+        pager: HashMap::from([
+            (
+                from_key_code(KeyCode::Up),
+                Action::Pager(ActionPager::NavLineUp),
+            ),
+            (
+                from_key_code(KeyCode::Down),
+                Action::Pager(ActionPager::NavLineDown),
+            ),
+            (
+                from_key_code(KeyCode::PageUp),
+                Action::Pager(ActionPager::NavPageUp),
+            ),
+            (
+                from_key_code(KeyCode::PageDown),
+                Action::Pager(ActionPager::NavPageDown),
+            ),
+            (
+                from_key_code(KeyCode::Char(' ')),
+                Action::Pager(ActionPager::NavPageDown),
+            ),
+            (
+                from_key_code(KeyCode::Home),
+                Action::Pager(ActionPager::NavHome),
+            ),
+            (
+                from_key_code(KeyCode::End),
+                Action::Pager(ActionPager::NavEnd),
+            ),
+            (
+                from_key_code(KeyCode::Char('/')),
+                Action::Pager(ActionPager::SearchOpen),
+            ),
+            (
+                from_key_code(KeyCode::Char('n')),
+                Action::Pager(ActionPager::SearchNext),
+            ),
+            (
+                from_key_code_with_modifiers(KeyCode::Char('N'), KeyModifiers::SHIFT),
+                Action::Pager(ActionPager::SearchPrev),
+            ),
+            (
+                from_key_code(KeyCode::Left),
+                Action::Pager(ActionPager::OutputPrev),
+            ),
+            (
+                from_key_code(KeyCode::Right),
+                Action::Pager(ActionPager::OutputNext),
+            ),
+            (
+                from_key_code(KeyCode::Char('q')),
+                Action::Pager(ActionPager::Close),
+            ),
+            (
+                from_key_code(KeyCode::Esc),
+                Action::Pager(ActionPager::Close),
+            ),
         ]),
+        terminal: HashMap::from([(
+            from_key_code(KeyCode::F(12)),
+            Action::Terminal(ActionTerminal::Unfocus),
+        )]),
         global: HashMap::from([
             (
                 Event::Key(KeyEvent {
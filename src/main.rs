@@ -1,11 +1,30 @@
+mod archive;
+mod columns;
+mod command_column;
+mod commands;
+mod completion;
+mod config;
+mod dir_size;
+mod disk_usage;
 mod file_info;
+mod git;
+mod history;
+mod ignore_rules;
+mod jobs;
 mod key_bindings;
+mod pager;
+mod pty;
+mod shell;
+mod size;
+mod sorting;
+mod users;
 mod vec2;
 mod vterm;
 
 use std::{
-    cmp::{min, Ordering},
-    env, fmt, fs, io, ops, path, process, str,
+    cmp::min,
+    collections::HashSet,
+    env, fs, io, ops, path, process, str,
     sync::{Arc, Mutex},
     time,
 };
@@ -19,7 +38,12 @@ use crossterm::{
     style::Stylize,
 };
 
-use key_bindings::{ActionCommand, ActionExplorer, ActionGlobal, KeyBindings};
+use columns::ColumnKind;
+use file_info::FileType;
+use key_bindings::{
+    ActionCommand, ActionDiskUsage, ActionExplorer, ActionGlobal, ActionPager, ActionTerminal,
+    KeyBindings,
+};
 use vec2::Vec2;
 use vterm::{Panel, VTerm};
 
@@ -29,6 +53,18 @@ use vterm::{Panel, VTerm};
 
 const DEBUG_MODE: bool = false;
 
+/// How long to wait for user input before checking on the background jobs.
+const EVENT_POLL_TIMEOUT: time::Duration = time::Duration::from_millis(100);
+
+/// How many command outputs are kept to be reopened.
+const OUTPUTS_MAX: usize = 20;
+
+/// How many lines of each command output are kept, the oldest ones are dropped first.
+const OUTPUT_LINES_MAX: usize = 10_000;
+
+/// Rows of the list of completions, the ones that don't fit are counted instead.
+const COMPLETIONS_ROWS_MAX: usize = 10;
+
 fn sat_add(value: usize, add: usize, saturation: usize) -> usize {
     // TODO: This break if saturates usize, but because we are using only for u16 it's fine.
     if value + add > saturation {
@@ -106,12 +142,25 @@ impl ScrollingWindow {
         self.viewport.into()
     }
 
+    /// Same window, but for a new list of entries.
+    fn reset(&mut self, new_entries_len: usize) {
+        self.resize(self.window_len, new_entries_len);
+    }
+
     fn selected(&self) -> usize {
         self.selected_entry
     }
 
+    /// Moves the selection to the entry at index `i`.
+    fn select(&mut self, i: usize) {
+        self.first();
+        for _ in 0..i {
+            self.down();
+        }
+    }
+
     fn down(&mut self) {
-        if self.selected_entry < self.entries_len - 1 && self.entries_len > 0 {
+        if self.entries_len > 0 && self.selected_entry < self.entries_len - 1 {
             self.selected_entry += 1;
             if self.entry_overflow(
                 sat_inc(self.selected_entry, self.entries_len - 1), // We decrement one because we show "..." when there is overflow
@@ -147,6 +196,9 @@ impl ScrollingWindow {
     }
 
     fn last(&mut self) {
+        if self.entries_len == 0 {
+            return;
+        }
         let last_entry_idx = self.entries_len - 1;
         self.selected_line = last_entry_idx;
         self.selected_entry = last_entry_idx;
@@ -191,37 +243,117 @@ enum StateMsg {
 enum Mode {
     Explorer,
     Command,
+    /// Asking the user for some text, what to do with it depends on the kind.
+    Prompt(PromptKind),
+    /// Navigating a scanned subtree by size.
+    DiskUsage,
+    /// Reading some text, like a diff or the output of a command.
+    Pager,
+    /// Typing into the embedded terminal.
+    Terminal,
 }
 
-type Entries = Vec<file_info::FileInfo>;
-
-#[derive(Debug)]
-enum Sorting {
-    Default,
-    Name,
-    Date,
-    Type,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    /// Name of the archive to compress the selection into.
+    ArchiveName,
+    /// Confirmation to delete the selected entry of the disk usage view.
+    DiskUsageDelete,
+    /// Columns to show, or settings of a column.
+    Columns,
+    /// Confirmation to discard the changes of the selection.
+    GitDiscard,
+    /// Text to find in the pager.
+    PagerSearch,
+    /// Text to find in the history of command mode.
+    HistorySearch,
 }
 
-impl fmt::Display for Sorting {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+impl PromptKind {
+    fn label(self) -> &'static str {
         match self {
-            Sorting::Default => fmt.write_str("Default"),
-            Sorting::Date => fmt.write_str("Date"),
-            Sorting::Name => fmt.write_str("Name"),
-            Sorting::Type => fmt.write_str("Type"),
+            PromptKind::ArchiveName => "Archive name (.tar.gz, .tar.zst or .zip):",
+            PromptKind::DiskUsageDelete => "Delete the selected entry? Type `y` to confirm:",
+            PromptKind::Columns => {
+                "Columns (eg.: `owner`, `name size owner`, `size width=min:6`, `mime command=file -b {}`):"
+            }
+            PromptKind::GitDiscard => {
+                "Discard the unstaged changes of the selection? Staged ones are kept. Type `y` to confirm:"
+            }
+            PromptKind::PagerSearch => "Search (case sensitive if it has uppercase letters):",
+            PromptKind::HistorySearch => {
+                "History search (Ctrl-R for older lines, Enter to edit the line found):"
+            }
+        }
+    }
+
+    /// The mode we go back to after the prompt is done.
+    fn parent(self) -> Mode {
+        match self {
+            PromptKind::ArchiveName | PromptKind::Columns | PromptKind::GitDiscard => {
+                Mode::Explorer
+            }
+            PromptKind::DiskUsageDelete => Mode::DiskUsage,
+            PromptKind::PagerSearch => Mode::Pager,
+            PromptKind::HistorySearch => Mode::Command,
         }
     }
 }
 
+/// Searching the history from command mode.
+struct HistorySearch {
+    /// Line of the history found.
+    found: Option<usize>,
+    /// What was in the prompt before searching, it's restored if nothing is picked.
+    typed: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GitOperation {
+    Stage,
+    Unstage,
+    /// Throws away the changes in the working tree.
+    Discard,
+}
+
+type Entries = Vec<file_info::FileInfo>;
+
 struct Dune {
     pub vterm: Arc<Mutex<VTerm>>,
     should_quit: bool,
 
     entries: Entries,
     entries_scrolling_window: ScrollingWindow,
-
-    sorting: Sorting,
+    /// Entries marked by the user, actions work on these instead of the selected entry.
+    marked: HashSet<path::PathBuf>,
+    jobs: jobs::Jobs,
+    dir_sizes: dir_size::DirSizes,
+    command_values: command_column::CommandValues,
+    git_status: git::GitStatus,
+    git_diff: git::GitDiff,
+    disk_usage: Option<disk_usage::DiskUsage>,
+    pager: Option<pager::Pager>,
+    commands: commands::Commands,
+    /// Outputs of the last commands that printed something, oldest first.
+    outputs: Vec<(commands::CommandId, pager::Pager)>,
+    /// Which of `outputs` the pager shows, if any.
+    output_idx: Option<usize>,
+    /// The pager shows the list of commands, it's updated as they change.
+    commands_list_open: bool,
+    /// The command just run, its output is opened in the pager once it has more than a line.
+    /// Until another key is pressed, not to take the focus from what the user is doing.
+    output_auto_open: Option<commands::CommandId>,
+    /// The command the state panel reports on, and the report, it's only shown while that's still in the panel.
+    state_command: Option<(commands::CommandId, String)>,
+    history: history::History,
+    history_search: Option<HistorySearch>,
+    /// What the last word of the prompt could be completed with, listed above it.
+    completions: Vec<String>,
+    /// Shell of the embedded terminal, it keeps running while hidden.
+    pty: Option<pty::Pty>,
+    pty_visible: bool,
+
+    sorting: sorting::Sorting,
     curr_dir: file_info::FileInfo,
     delta_time: time::Duration,
     state: StateMsg,
@@ -229,12 +361,27 @@ struct Dune {
     prompt: String,
     cursor: (usize, usize),
     key_bindings: KeyBindings,
+    users: users::UserNames,
+    /// Show the full dates of the selected entry in the state panel, when there is no message.
+    show_details: bool,
+    /// List dot files.
+    show_hidden: bool,
+    /// Don't list what `.gitignore`, `.ignore` and the git excludes ignore.
+    hide_ignored: bool,
+    /// Entries of the current directory not listed because of `show_hidden` or `hide_ignored`.
+    hidden_count: usize,
+    /// For the sizes outside of the columns.
+    size_units: size::SizeUnits,
+    command_settings: shell::Settings,
     // Panels
     panel_header: Panel,
-    panel_file_name: Panel,
-    panel_file_permissions: Panel,
-    panel_file_last_modified: Panel,
-    panel_file_size: Panel,
+    /// Columns of the entries table, in the order they are shown.
+    columns: Vec<columns::Column>,
+    /// The whole entries table, for what isn't drawn in a column.
+    panel_entries: Panel,
+    /// The embedded terminal, below the entries table.
+    panel_pty: Panel,
+    panel_completions: Panel,
     panel_state: Panel,
     panel_prompt: Panel,
 }
@@ -243,28 +390,107 @@ impl Dune {
     fn new(
         vterm: Arc<Mutex<VTerm>>,
         key_bindings: KeyBindings,
+        config: Result<config::Config, String>,
         starting_path: path::PathBuf,
     ) -> Self {
+        let (config, mut errors) = match config {
+            Ok(config) => (config, Vec::new()),
+            Err(e) => (config::Config::default(), vec![e]),
+        };
+        let size_units = config
+            .get("", "size_units")
+            .map(str::parse)
+            .transpose()
+            .unwrap_or_else(|e| {
+                errors.push(e);
+                None
+            })
+            .unwrap_or_default();
+        let (columns, columns_errors) = columns::from_config(&config, size_units, vterm.clone());
+        errors.extend(columns_errors);
+        let (sorting, sorting_errors) = sorting::Sorting::from_config(&config);
+        errors.extend(sorting_errors);
+        let (command_settings, command_errors) = shell::Settings::from_config(&config);
+        errors.extend(command_errors);
+        let show_hidden = config
+            .get("", "show_hidden")
+            .map(config::parse_bool)
+            .transpose()
+            .unwrap_or_else(|e| {
+                errors.push(e);
+                None
+            })
+            .unwrap_or(true);
+        let hide_ignored = config
+            .get("", "hide_ignored")
+            .map(config::parse_bool)
+            .transpose()
+            .unwrap_or_else(|e| {
+                errors.push(e);
+                None
+            })
+            .unwrap_or(false);
+        let (history, history_error) = match history::History::load(command_settings.history_size) {
+            Ok(history) => (history, None),
+            // It's not saved, not to lose what couldn't be read.
+            Err(e) => (
+                history::History::new(command_settings.history_size),
+                Some(e),
+            ),
+        };
+
         Self {
             vterm: vterm.clone(),
             should_quit: false,
             entries: Vec::new(),
+            marked: HashSet::new(),
+            jobs: jobs::Jobs::new(),
+            dir_sizes: dir_size::DirSizes::new(),
+            command_values: command_column::CommandValues::new(),
+            git_status: git::GitStatus::new(),
+            git_diff: git::GitDiff::new(),
+            disk_usage: None,
+            pager: None,
+            commands: commands::Commands::new(),
+            outputs: Vec::new(),
+            output_idx: None,
+            commands_list_open: false,
+            output_auto_open: None,
+            state_command: None,
+            history,
+            history_search: None,
+            completions: Vec::new(),
+            pty: None,
+            pty_visible: false,
             curr_dir: starting_path
                 .try_into()
                 .expect("could not open current directory"),
-            sorting: Sorting::Default,
+            sorting,
             delta_time: time::Duration::ZERO,
-            state: StateMsg::Ok,
+            state: match (errors.first(), history_error) {
+                (Some(e), _) => StateMsg::Error(format!("Invalid config: {e}")),
+                (None, Some(e)) => StateMsg::Error(format!(
+                    "Tried to read the command history, but failed because {e}"
+                )),
+                (None, None) => StateMsg::Ok,
+            },
             mode: Mode::Explorer,
             entries_scrolling_window: ScrollingWindow::new(0, 0), // Hack cus we can't reference self.entries here yet.
             prompt: "".to_owned(),
             cursor: (0, 0),
             key_bindings,
+            users: users::UserNames::new(),
+            show_details: false,
+            show_hidden,
+            hide_ignored,
+            hidden_count: 0,
+            size_units,
+            command_settings,
             panel_header: Panel::new(vterm.clone()),
-            panel_file_name: Panel::new(vterm.clone()),
-            panel_file_permissions: Panel::new(vterm.clone()),
-            panel_file_last_modified: Panel::new(vterm.clone()),
-            panel_file_size: Panel::new(vterm.clone()),
+            columns,
+            panel_entries: Panel::new(vterm.clone()),
+            panel_pty: Panel::new(vterm.clone()),
+            panel_completions: Panel::new(vterm.clone()),
             panel_state: Panel::new(vterm.clone()),
             panel_prompt: Panel::new(vterm.clone()),
         }
@@ -274,7 +500,7 @@ impl Dune {
     /// Returns the path the user is currently in as Ok(path)
     pub fn run(&mut self) -> io::Result<&path::Path> {
         VTerm::clear()?;
-        self.update_entries()?;
+        self.refresh_entries();
         self.update_panels_size();
         self.render()?;
 
@@ -322,15 +548,27 @@ impl Dune {
         }
 
         match self.mode {
-            Mode::Explorer => {
+            Mode::Explorer | Mode::DiskUsage | Mode::Pager => {
                 VTerm::cursor_hide()?;
             }
 
-            Mode::Command => {
+            Mode::Command | Mode::Prompt(_) => {
                 self.panel_prompt
                     .draw_text(&self.prompt, 0, 0, style::ContentStyle::new());
                 VTerm::cursor_show()?;
             }
+
+            Mode::Terminal => {
+                if self
+                    .pty
+                    .as_ref()
+                    .is_some_and(|pty| pty.screen().hide_cursor())
+                {
+                    VTerm::cursor_hide()?;
+                } else {
+                    VTerm::cursor_show()?;
+                }
+            }
         }
 
         // Draw state
@@ -349,7 +587,10 @@ impl Dune {
                     format!("ERROR: {msg}."),
                     style::ContentStyle::new().on_dark_red().white().bold(),
                 ),
-                StateMsg::Ok => ("".to_owned(), style::ContentStyle::new().on_white().black()),
+                StateMsg::Ok => (
+                    self.details().unwrap_or_default(),
+                    style::ContentStyle::new().on_white().black(),
+                ),
                 StateMsg::Info(msg) => (
                     msg.to_owned(),
                     style::ContentStyle::new().on_white().black().bold(),
@@ -368,31 +609,136 @@ impl Dune {
         let mode = match self.mode {
             Mode::Command => "Command Mode",
             Mode::Explorer => "Explorer Mode",
+            Mode::Prompt(_) => "Prompt Mode",
+            Mode::DiskUsage => "Disk Usage Mode",
+            Mode::Pager => "Pager Mode",
+            Mode::Terminal => "Terminal Mode",
         };
-        let text = format!(
+        let mut text = format!(
             "{path}: (total {total})",
             path = self.curr_dir.path_str(),
             total = self.entries.len()
         );
+        if self.hidden_count > 0 {
+            text.push_str(&format!(" (hidden {hidden})", hidden = self.hidden_count));
+        }
+        if let Some(repo) = self.git_status.repo.as_ref() {
+            text.push_str(&format!(
+                " [{branch}",
+                branch = repo.branch.as_deref().unwrap_or("detached")
+            ));
+            if let Some((ahead, behind)) = repo.ahead_behind {
+                text.push_str(&format!(" ↑{ahead} ↓{behind}"));
+            }
+            text.push(']');
+        }
+        if let Some(pager) = self.pager.as_ref() {
+            text = format!(
+                "{title}: ({lines} lines)",
+                title = pager.title,
+                lines = pager.line_count()
+            );
+            if let Some(idx) = self.output_idx {
+                text.push_str(&format!(
+                    " (output {n} of {count})",
+                    n = idx + 1,
+                    count = self.outputs.len()
+                ));
+            }
+            if let Some(search) = pager.search_status() {
+                text.push_str(&format!(" {search}"));
+            }
+        } else if let Some(node) = self.disk_usage.as_ref().and_then(|du| du.node()) {
+            text = format!(
+                "Disk usage of {path}: (total {size}, {items} items)",
+                path = node.path.to_string_lossy(),
+                size = self.size_units.format(node.size),
+                items = node.items,
+            );
+        } else if !self.marked.is_empty() {
+            // Directories only count if their size was calculated.
+            let total = self
+                .entries
+                .iter()
+                .filter(|e| self.marked.contains(e.path()))
+                .map(|e| e.size())
+                .sum::<u64>();
+            text.push_str(&format!(
+                " (marked {marked}, {total})",
+                marked = self.marked.len(),
+                total = self.size_units.format(total)
+            ));
+        }
         self.panel_header
             .draw_text(&text, 0, 0, style.bold().black());
         let w = self.vterm.lock().unwrap().width;
-        self.panel_header
-            .draw_text(mode, w - 1 - mode.len(), 0, style.bold().black());
+        let mut status = format!("Sort: {sorting} | {mode}", sorting = self.sorting);
+        let running = self.commands.running().count();
+        if running > 0 {
+            status = format!("[{running} running] {status}");
+        }
+        for job in self.jobs.running() {
+            status = format!(
+                "[{name} {percent}%] {status}",
+                name = job.name,
+                percent = job.percent()
+            );
+        }
+        self.panel_header.draw_text(
+            &status,
+            w.saturating_sub(1 + status.len()),
+            0,
+            style.bold().black(),
+        );
+
+        self.render_pty();
+
+        if let Some(pager) = self.pager.as_ref() {
+            let width = self.panel_entries.width;
+            for (line_idx, idx) in pager.visible().enumerate() {
+                let mut x = 0;
+                for (text, style) in pager.spans(idx) {
+                    if x >= width {
+                        break;
+                    }
+                    let text = text.chars().take(width - x).collect::<String>();
+                    self.panel_entries.draw_text(&text, x, line_idx, style);
+                    x += text.chars().count();
+                }
+            }
+            self.render_completions();
+            self.render_terminal()?;
+            self.vterm
+                .lock()
+                .unwrap()
+                .cursor_move(self.cursor.0, self.cursor.1)?;
+            return Ok(());
+        }
+
+        if self.disk_usage.is_some() {
+            self.render_disk_usage();
+            self.render_completions();
+            self.render_terminal()?;
+            self.vterm
+                .lock()
+                .unwrap()
+                .cursor_move(self.cursor.0, self.cursor.1)?;
+            return Ok(());
+        }
 
         // Draw entries
         let visible_entries_range = self.entries_scrolling_window.visible();
         for (line_idx, entry_idx) in visible_entries_range.clone().enumerate() {
             if line_idx == 0 && entry_idx > 0 {
-                self.panel_file_name
+                self.panel_entries
                     .draw_text("...", 3, line_idx, style::ContentStyle::new());
                 continue;
             }
 
-            if line_idx == self.panel_file_name.height - 1
+            if line_idx == self.panel_entries.height - 1
                 && self.entries.len() > visible_entries_range.end
             {
-                self.panel_file_name
+                self.panel_entries
                     .draw_text("...", 3, line_idx, style::ContentStyle::new());
                 continue;
             }
@@ -400,6 +746,7 @@ impl Dune {
             self.render_entry(entry_idx, line_idx);
         }
 
+        self.render_completions();
         self.render_terminal()?;
 
         // Cursor
@@ -411,29 +758,218 @@ impl Dune {
         Ok(())
     }
 
+    /// Lists the completions of the prompt in columns, right above the state panel.
+    fn render_completions(&mut self) {
+        if self.completions.is_empty() || self.mode != Mode::Command {
+            return;
+        }
+        let (w, h) = self.vterm.lock().unwrap().size();
+
+        let col_w = 2 + self
+            .completions
+            .iter()
+            .map(|c| c.chars().count())
+            .max()
+            .unwrap_or_default();
+        let cols = (w / col_w).max(1);
+        let rows = self.completions.len().div_ceil(cols);
+        let rows_shown = rows.min(COMPLETIONS_ROWS_MAX);
+        // The last cell says how many are left out.
+        let shown = if rows > rows_shown {
+            rows_shown * cols - 1
+        } else {
+            self.completions.len()
+        };
+        let more = format!("... {n} more", n = self.completions.len() - shown);
+
+        self.panel_completions
+            .update_size(0, h - 2 - rows_shown, w, rows_shown);
+        let style = style::ContentStyle::new().on_dark_grey().white();
+        self.panel_completions.fill(' ', style);
+        let texts = self.completions[..shown]
+            .iter()
+            .map(String::as_str)
+            .chain((shown < self.completions.len()).then_some(more.as_str()));
+        for (i, text) in texts.enumerate() {
+            let x = 1 + (i % cols) * col_w;
+            if x >= w {
+                continue;
+            }
+            let text = text.chars().take(w - x).collect::<String>();
+            self.panel_completions.draw_text(&text, x, i / cols, style);
+        }
+    }
+
+    /// Draws the embedded terminal, if it's visible, and moves the cursor to it when focused.
+    fn render_pty(&mut self) {
+        let Some(pty) = self.pty.as_ref().filter(|_| self.pty_visible) else {
+            return;
+        };
+        if self.panel_pty.height == 0 {
+            return;
+        }
+
+        let title = if self.mode == Mode::Terminal {
+            " Terminal: F12 to go back"
+        } else {
+            " Terminal: ` to type, ~ to hide"
+        };
+        let style = style::ContentStyle::new().on_grey().black().bold();
+        self.panel_pty.draw_text(
+            &format!("{title:<width$}", width = self.panel_pty.width),
+            0,
+            0,
+            style,
+        );
+
+        let screen = pty.screen();
+        let (rows, cols) = screen.size();
+        let rows = usize::from(rows).min(self.panel_pty.height - 1);
+        let cols = usize::from(cols).min(self.panel_pty.width);
+        for row in 0..rows {
+            for col in 0..cols {
+                let Some(cell) = screen.cell(row as u16, col as u16) else {
+                    continue;
+                };
+                if cell.is_wide_continuation() {
+                    continue;
+                }
+                // Combining characters would take cells of their own.
+                let ch = cell.contents().chars().next().unwrap_or(' ');
+                self.panel_pty
+                    .draw_text(&ch.to_string(), col, row + 1, pty::cell_style(cell));
+            }
+        }
+
+        if self.mode == Mode::Terminal {
+            let (row, col) = screen.cursor_position();
+            // The terminal is right above the state panel.
+            let top = self.vterm.lock().unwrap().height - 2 - self.panel_pty.height;
+            self.cursor = (usize::from(col), top + 1 + usize::from(row));
+        }
+    }
+
+    /// Every date of the selected entry, to the second.
+    fn details(&self) -> Option<String> {
+        if !self.show_details || self.mode != Mode::Explorer {
+            return None;
+        }
+
+        let entry = self.entries.get(self.entries_scrolling_window.selected())?;
+        if entry.error().is_some() {
+            return None;
+        }
+        let dates = [
+            file_info::DateKind::Modified,
+            file_info::DateKind::Changed,
+            file_info::DateKind::Accessed,
+            file_info::DateKind::Birth,
+        ]
+        .into_iter()
+        .filter_map(|kind| {
+            let date = entry.date(kind)?;
+            Some(format!(
+                "{name}: {date}",
+                name = kind.name(),
+                date = date.format("%Y-%m-%d %H:%M:%S %Z")
+            ))
+        })
+        .collect::<Vec<_>>();
+        Some(dates.join("  "))
+    }
+
+    fn render_disk_usage(&mut self) {
+        let Some(du) = self.disk_usage.as_ref() else {
+            return;
+        };
+
+        let Some(node) = du.node() else {
+            self.panel_entries.draw_text(
+                &format!("Scanning... {scanned} entries", scanned = du.scanned),
+                0,
+                0,
+                style::ContentStyle::new().bold(),
+            );
+            return;
+        };
+
+        const BAR_LEN: usize = 10;
+        let visible = du.window.visible();
+        for (line_idx, child_idx) in visible.clone().enumerate() {
+            if (line_idx == 0 && child_idx > 0)
+                || (line_idx == self.panel_entries.height - 1 && node.children.len() > visible.end)
+            {
+                self.panel_entries
+                    .draw_text("...", 3, line_idx, style::ContentStyle::new());
+                continue;
+            }
+
+            let child = &node.children[child_idx];
+            let ratio = if node.size == 0 {
+                0.0
+            } else {
+                child.size as f64 / node.size as f64
+            };
+            let filled = (ratio * BAR_LEN as f64).round() as usize;
+            let items = if child.is_dir {
+                format!("{items} items", items = child.items)
+            } else {
+                "".to_owned()
+            };
+            let text = format!(
+                "{size:>9} [{bar:<BAR_LEN$}] {percent:5.1}% {items:>12}  {name}{slash}",
+                size = self.size_units.format(child.size),
+                bar = "#".repeat(filled),
+                percent = ratio * 100.0,
+                name = child.name,
+                slash = if child.is_dir { "/" } else { "" },
+            );
+
+            let style = if child_idx == du.window.selected() {
+                style::ContentStyle::new().bold().reverse()
+            } else {
+                style::ContentStyle::new().bold()
+            };
+            let style = if child.is_dir { style.cyan() } else { style };
+            self.panel_entries.draw_text(&text, 0, line_idx, style);
+        }
+    }
+
     fn render_entry(&mut self, entry_idx: usize, line_idx: usize) {
         let entry = &self.entries[entry_idx];
 
         let style = if entry_idx == self.entries_scrolling_window.selected() {
             match self.mode {
-                Mode::Command => style::ContentStyle::new().bold().on_dark_green(),
-                Mode::Explorer => style::ContentStyle::new().bold().reverse(),
+                Mode::Command | Mode::Prompt(_) => {
+                    style::ContentStyle::new().bold().on_dark_green()
+                }
+                Mode::Explorer | Mode::DiskUsage | Mode::Pager | Mode::Terminal => {
+                    style::ContentStyle::new().bold().reverse()
+                }
             }
         } else {
             style::ContentStyle::new().bold()
         };
 
-        let mode = entry.mode();
+        if let Some(err) = entry.error() {
+            // There is nothing else to show.
+            let text = format!("{name} ({err})", name = entry.name());
+            self.panel_entries
+                .draw_text(&text, 0, line_idx, style.dark_red());
+            return;
+        }
 
-        let style = if entry.is_dir() {
-            style.cyan()
-        } else if mode & 0o001 == 1 {
-            // Is executable
-            style.green()
-        } else if entry.is_read_only() {
-            style.grey()
-        } else {
-            style
+        let style = match entry.file_type() {
+            FileType::Symlink if entry.is_broken_link() => style.red(),
+            FileType::Symlink => style.magenta(),
+            FileType::Dir => style.cyan(),
+            FileType::Fifo => style.dark_yellow(),
+            FileType::Socket => style.dark_magenta(),
+            FileType::BlockDevice => style.blue(),
+            FileType::CharDevice => style.dark_cyan(),
+            FileType::Regular if entry.is_executable() => style.green(),
+            FileType::Regular if entry.is_read_only() => style.grey(),
+            FileType::Regular => style,
         };
 
         let style = if entry.name().starts_with('.') {
@@ -443,46 +979,51 @@ impl Dune {
             style
         };
 
-        let mut name = entry.name().to_string();
-        if name.len() > self.panel_file_name.width {
-            // TODO: Maybe do this with `format!`?
-            name.truncate(self.panel_file_name.width.saturating_sub(3));
-            name.push_str("...");
-        }
-        self.panel_file_name.draw_text(&name, 0, line_idx, style);
-
-        self.panel_file_last_modified.draw_text(
-            entry
-                .last_modified()
-                .format("%e %b %y")
-                .to_string()
-                .as_str(),
-            0,
-            line_idx,
-            style::ContentStyle::new().dim(),
-        );
-
-        self.panel_file_size.draw_text(
-            &entry.pretty_size(),
-            0,
-            line_idx,
-            style::ContentStyle::new().dim(),
-        );
+        let style = if self.marked.contains(entry.path()) {
+            style.yellow()
+        } else {
+            style
+        };
 
-        let mut permissions = String::with_capacity(12); // d rwxrwxrwx
-        permissions.push(if entry.is_dir() { 'd' } else { '-' });
-        permissions.push(' ');
-        for i in 0..3 {
-            permissions.push(if mode >> i & 0o1 > 0 { 'r' } else { '-' });
-            permissions.push(if mode >> i & 0o2 > 0 { 'w' } else { '-' });
-            permissions.push(if mode >> i & 0o4 > 0 { 'x' } else { '-' });
+        for column in self.columns.iter_mut().filter(|c| c.visible) {
+            let text = if column.kind == ColumnKind::Size
+                && entry.is_dir()
+                && self.dir_sizes.is_pending(entry)
+            {
+                None
+            } else {
+                // Only computed for the visible rows.
+                column.value(
+                    entry,
+                    &mut self.users,
+                    &mut self.command_values,
+                    self.git_status.repo.as_ref(),
+                )
+            };
+            let text = text.unwrap_or_else(|| "...".to_owned());
+            let column_style = match column.kind {
+                ColumnKind::Name => style,
+                ColumnKind::Git => {
+                    let style = style::ContentStyle::new().bold();
+                    match self
+                        .git_status
+                        .repo
+                        .as_ref()
+                        .and_then(|r| r.get(entry.path()))
+                    {
+                        Some(git::FileStatus::Conflicted) => style.red(),
+                        Some(git::FileStatus::Modified) => style.yellow(),
+                        Some(git::FileStatus::Staged) => style.green(),
+                        Some(git::FileStatus::Untracked) => style.cyan(),
+                        Some(git::FileStatus::Ignored) | None => style.dim(),
+                    }
+                }
+                _ => style::ContentStyle::new().dim(),
+            };
+            column
+                .panel
+                .draw_text(&column.align_text(&text), 0, line_idx, column_style);
         }
-        self.panel_file_permissions.draw_text(
-            permissions.as_str(),
-            0,
-            line_idx,
-            style::ContentStyle::new().dim(),
-        );
     }
 
     fn update_panels_size(&mut self) {
@@ -496,60 +1037,211 @@ impl Dune {
 
         self.panel_header.update_size(0, 0, w, 1);
 
-        {
-            const PERMISSIONS_LEN: usize = 12;
-            const SIZE_LEN: usize = 8;
-            const LAST_MODIFIED_LEN: usize = 10;
-            let mut len_left = w; // Lenght of the fixed elements on the table
-
-            len_left = len_left.saturating_sub(PERMISSIONS_LEN);
-            self.panel_file_permissions
-                .update_size(len_left, 1, PERMISSIONS_LEN, h - 3);
-
-            len_left = len_left.saturating_sub(SIZE_LEN);
-            self.panel_file_size
-                .update_size(len_left, 1, SIZE_LEN, h - 3);
-
-            len_left = len_left.saturating_sub(LAST_MODIFIED_LEN);
-            self.panel_file_last_modified
-                .update_size(len_left, 1, LAST_MODIFIED_LEN, h - 3);
-
-            self.panel_file_name.update_size(0, 1, len_left, h - 3);
+        // The terminal takes the bottom half of the table area, its first line is a title bar.
+        let table_h = h - 3;
+        let pty_h = if self.pty.is_some() && self.pty_visible {
+            table_h / 2
+        } else {
+            0
+        };
+        columns::layout(&mut self.columns, 0, 1, w, table_h - pty_h);
+        self.panel_entries.update_size(0, 1, w, table_h - pty_h);
+        self.panel_pty.update_size(0, 1 + table_h - pty_h, w, pty_h);
+        if let Some(pty) = self.pty.as_mut().filter(|_| pty_h > 1) {
+            pty.resize((pty_h - 1) as u16, w as u16);
         }
 
         self.panel_state.update_size(0, h - 2, w, 1);
         self.panel_prompt.update_size(0, h - 1, w, 1);
 
         self.entries_scrolling_window
-            .resize(self.panel_file_name.height, self.entries.len());
+            .resize(self.panel_entries.height, self.entries.len());
+        if let Some(du) = self.disk_usage.as_mut() {
+            let len = du.node().map(|n| n.children.len()).unwrap_or(0);
+            du.window.resize(self.panel_entries.height, len);
+        }
+        if let Some(pager) = self.pager.as_mut() {
+            pager.resize(self.panel_entries.height);
+        }
     }
 
+    /// Lists the current directory.
+    /// Only fails if the directory itself can't be listed, in that case the previous listing is kept.
+    /// Entries that fail to load are listed with their error.
     fn update_entries(&mut self) -> io::Result<()> {
         // Other entries
         let curr_dir = env::current_dir()?;
+        let read_dir = fs::read_dir(&curr_dir)?;
+        let curr_dir_info = curr_dir.clone().try_into()?;
 
         self.entries.clear();
-        for entry in fs::read_dir(&curr_dir)? {
-            self.entries.push(entry?.try_into()?);
+        for entry in read_dir {
+            let info = match entry {
+                Ok(entry) => {
+                    let path = entry.path();
+                    file_info::FileInfo::try_from(entry)
+                        .unwrap_or_else(|e| file_info::FileInfo::from_error(path, &e))
+                }
+                Err(e) => {
+                    file_info::FileInfo::from_error(curr_dir.join(file_info::INVALID_FILE), &e)
+                }
+            };
+            self.entries.push(info);
+        }
+
+        let total = self.entries.len();
+        if !self.show_hidden {
+            self.entries.retain(|e| !e.name().starts_with('.'));
+        }
+        if self.hide_ignored {
+            let (rules, errors) = ignore_rules::IgnoreRules::for_dir(&curr_dir);
+            self.entries
+                .retain(|e| !rules.is_ignored(e.path(), e.is_dir()));
+            if let Some(err) = errors.first() {
+                self.state = StateMsg::Error(format!(
+                    "Tried to read the ignore rules, but failed because {err}"
+                ));
+            }
+        }
+        self.hidden_count = total - self.entries.len();
+        self.git_status.request(curr_dir.clone());
+
+        // Forget marks of entries that are gone (or from the directory we just left).
+        self.marked
+            .retain(|path| self.entries.iter().any(|e| e.path() == path));
+        for entry in self.entries.iter_mut() {
+            if let Some(size) = self.dir_sizes.get(entry) {
+                entry.set_dir_size(size);
+            } else if self.dir_sizes.auto {
+                self.dir_sizes.request(entry);
+            }
         }
         self.entries_scrolling_window
-            .resize(self.panel_file_name.height, self.entries.len());
+            .resize(self.panel_entries.height, self.entries.len());
 
-        self.curr_dir = curr_dir.try_into()?;
+        self.curr_dir = curr_dir_info;
 
         self.sort_entries();
 
         Ok(())
     }
 
+    /// Lists the current directory again, keeping the same entry selected, showing an error if it can't.
+    fn refresh_entries(&mut self) {
+        let selected = self
+            .entries
+            .get(self.entries_scrolling_window.selected())
+            .map(|e| e.path().to_path_buf());
+        if let Err(err) = self.update_entries() {
+            self.state = StateMsg::Error(format!(
+                "Could not list `{dir}`: {err}",
+                dir = self.curr_dir.path_str()
+            ));
+        } else if let Some(idx) =
+            selected.and_then(|path| self.entries.iter().position(|e| e.path() == path))
+        {
+            self.entries_scrolling_window.select(idx);
+        }
+    }
+
+    /// Moves into `dir` and lists it.
+    /// If it can't be listed we go back, so the previous listing is still valid.
+    fn enter_dir<P: AsRef<path::Path>>(&mut self, dir: P) -> io::Result<()> {
+        cd(dir)?;
+        if let Err(err) = self.update_entries() {
+            let _ = cd(self.curr_dir.path());
+            return Err(err);
+        }
+        if let Some(pty) = self.pty.as_mut() {
+            pty.set_dir(self.curr_dir.path());
+        }
+        Ok(())
+    }
+
     fn render_terminal(&mut self) -> io::Result<()> {
         self.vterm.lock().unwrap().flush()?;
         self.vterm.lock().unwrap().cursor_move(0, 1)
     }
 
     fn poll_events(&mut self) -> io::Result<()> {
-        self.handle_event(event::read()?)
+        // Don't block forever waiting for input, the background jobs need to be checked.
+        if event::poll(EVENT_POLL_TIMEOUT)? {
+            self.handle_event(event::read()?)?;
+        }
         // TODO: Wait for a few millis to se if any event comes right after the first one.
+
+        self.poll_dir_sizes();
+        self.command_values.poll();
+        if let Some(Err(e)) = self.git_status.poll() {
+            self.state = StateMsg::Error(format!(
+                "Tried to read the git status, but failed because {e}"
+            ));
+        }
+        self.poll_git_diff();
+        self.poll_disk_usage();
+        self.poll_jobs();
+        self.poll_commands();
+        self.poll_pty();
+        Ok(())
+    }
+
+    fn poll_disk_usage(&mut self) {
+        let Some(du) = self.disk_usage.as_mut() else {
+            return;
+        };
+
+        match du.poll() {
+            Some(Ok(())) => self.state = StateMsg::Ok,
+            Some(Err(e)) => {
+                self.state = StateMsg::Error(format!(
+                    "Could not scan `{path}`: {e}",
+                    path = self.curr_dir.path_str()
+                ));
+                self.disk_usage = None;
+                self.mode = Mode::Explorer;
+            }
+            None => {}
+        }
+    }
+
+    fn disk_usage_close(&mut self) {
+        self.disk_usage = None;
+        // Things might have been deleted.
+        self.refresh_entries();
+    }
+
+    fn poll_dir_sizes(&mut self) {
+        let sizes = self.dir_sizes.poll();
+        if sizes.is_empty() {
+            return;
+        }
+
+        for (path, size) in sizes {
+            if let Some(entry) = self.entries.iter_mut().find(|e| e.path() == path) {
+                entry.set_dir_size(size);
+            }
+        }
+        if self.sorting.key == sorting::SortKey::Size {
+            self.sort_entries();
+        }
+    }
+
+    fn poll_jobs(&mut self) {
+        let finished = self.jobs.poll();
+        if finished.is_empty() {
+            return;
+        }
+
+        for (job, result) in finished {
+            if let Some(du) = self.disk_usage.as_mut() {
+                du.delete_finished(job.id, result.is_ok());
+            }
+            self.state = match result {
+                Ok(msg) => StateMsg::Info(msg),
+                Err(msg) => StateMsg::Error(format!("{name}: {msg}", name = job.name)),
+            };
+        }
+        self.refresh_entries();
     }
 
     fn handle_event(&mut self, evt: event::Event) -> io::Result<()> {
@@ -563,20 +1255,96 @@ impl Dune {
             return Ok(());
         }
 
+        if let event::Event::Key(_) = evt {
+            self.output_auto_open = None;
+        }
+
+        if self.mode == Mode::Terminal {
+            if let Some(ActionTerminal::Unfocus) = self.key_bindings.get_terminal(&evt) {
+                self.mode = Mode::Explorer;
+                return Ok(());
+            }
+            if let (event::Event::Key(key), Some(pty)) = (&evt, self.pty.as_mut()) {
+                if let Err(e) = pty.key(key) {
+                    self.state = StateMsg::Error(format!(
+                        "Tried to type into the terminal, but failed because {e}"
+                    ));
+                }
+            }
+            return Ok(());
+        }
+
+        if self.mode == Mode::Command {
+            let action = self.key_bindings.get_command(&evt).copied();
+            if matches!(evt, event::Event::Key(_)) {
+                // They were for what was typed before.
+                self.completions.clear();
+            }
+            // With nothing typed Tab still changes the mode.
+            if let Some(ActionCommand::Complete) = action.filter(|_| !self.prompt.trim().is_empty())
+            {
+                self.complete();
+                return Ok(());
+            }
+        }
+
         if let Some(action) = self.key_bindings.get_global(&evt) {
             match action {
                 ActionGlobal::Quit => {
+                    // While a command runs Ctrl-C is for it, like in a shell.
+                    if let Some(command) = self.command_foreground() {
+                        self.state = match self.commands.interrupt(command.id) {
+                            Ok(()) => StateMsg::Info(format!(
+                                "Interrupted `{command}` (pid {pid})",
+                                command = command.command,
+                                pid = command.pid
+                            )),
+                            Err(e) => StateMsg::Error(format!(
+                                "Tried to interrupt `{command}`, but failed because {e}",
+                                command = command.command
+                            )),
+                        };
+                        return Ok(());
+                    }
                     self.should_quit = true;
                 }
                 ActionGlobal::ModeChange => {
                     // Toggle mode
-                    self.mode = if self.mode == Mode::Explorer {
-                        self.cursor = (0, self.vterm.lock().unwrap().height - 1);
-                        self.state = StateMsg::Info("Command:".into());
-                        Mode::Command
-                    } else {
-                        self.state = StateMsg::Ok;
-                        Mode::Explorer
+                    self.mode = match self.mode {
+                        Mode::Explorer => {
+                            self.cursor = (0, self.vterm.lock().unwrap().height - 1);
+                            self.state = StateMsg::Info("Command:".into());
+                            Mode::Command
+                        }
+                        Mode::Command => {
+                            self.history.reset();
+                            self.state = StateMsg::Ok;
+                            Mode::Explorer
+                        }
+                        Mode::Prompt(kind) => {
+                            // Cancelled, the text was only meant for the prompt.
+                            self.prompt.clear();
+                            self.state = StateMsg::Ok;
+                            if let Some(search) = self.history_search.take() {
+                                self.prompt_set(search.typed);
+                                self.state = StateMsg::Info("Command:".into());
+                            }
+                            kind.parent()
+                        }
+                        Mode::DiskUsage => {
+                            self.disk_usage_close();
+                            self.state = StateMsg::Ok;
+                            Mode::Explorer
+                        }
+                        Mode::Pager => {
+                            self.pager = None;
+                            self.output_idx = None;
+                            self.commands_list_open = false;
+                            self.state = StateMsg::Ok;
+                            Mode::Explorer
+                        }
+                        // Tab is typed into the terminal, it never gets here.
+                        Mode::Terminal => Mode::Explorer,
                     };
                 }
             }
@@ -588,46 +1356,37 @@ impl Dune {
                     // If known command
                     match action {
                         ActionCommand::Execute => {
-                            // TODO: This require better input handling
-                            let mut prompt_split = self.prompt.split(' ');
-                            if let Some(cmd) = prompt_split.next() {
-                                let args = prompt_split.collect::<Vec<&str>>();
-                                let mut exec = process::Command::new(cmd);
-                                // TODO: Allow patterns in args for selected file
-                                let exec = exec.args(args);
-                                // TODO: How are we dealing with user interaction?
-                                // TODO: Don't quit on error (if command doesn't exist it will error).
-                                let output = exec.output()?;
-                                // TODO: Extract signal from ext code.
-                                let exit_code = output.status.code().unwrap_or(0);
-                                let pretty_command = format!(
-                                    "{program} {args}",
-                                    program = exec
-                                        .get_program()
-                                        .to_str()
-                                        .unwrap_or("<INVALID-UTF8-PROGRAM>"),
-                                    args = exec
-                                        .get_args()
-                                        .map(|arg| arg.to_str().unwrap_or("<INVALID-UTF8-ARG>"))
-                                        .collect::<Vec<_>>()
-                                        .join(" ")
-                                );
-                                if output.status.success() {
-                                    let stdout = str::from_utf8(&output.stdout).map_err(|e| {
-                                        io::Error::new(io::ErrorKind::InvalidData, e)
-                                    })?;
-                                    self.state = StateMsg::Info(format!(
-                                        "{pretty_command}: exit {exit_code}: {stdout}"
-                                    ));
-                                } else {
-                                    let stderr = str::from_utf8(&output.stderr).map_err(|e| {
-                                        io::Error::new(io::ErrorKind::InvalidData, e)
-                                    })?;
+                            let (input, interactive) =
+                                shell::strip_interactive_prefix(&self.prompt);
+                            let interactive =
+                                interactive || self.command_settings.is_interactive(input);
+                            let exec =
+                                shell::command(input, &self.placeholders(), self.curr_dir.path());
+                            let mut exec = match exec {
+                                Ok(exec) => exec,
+                                Err(e) => {
                                     self.state = StateMsg::Error(format!(
-                                        "{pretty_command}: exit {exit_code}: {stderr}"
+                                        "Tried to parse the command, but failed because {e}"
                                     ));
+                                    return Ok(());
+                                }
+                            };
+                            // Like in a shell, the prompt is emptied for the next line.
+                            let line = std::mem::take(&mut self.prompt);
+                            self.cursor.0 = 0;
+                            let history_result = self.history.push(&line);
+                            if let Some(exec) = &mut exec {
+                                if interactive {
+                                    self.command_run_attached(exec)?;
+                                    self.refresh_entries();
+                                } else {
+                                    self.command_spawn(exec);
                                 }
-                                self.update_entries()?;
+                            }
+                            if let Err(e) = history_result {
+                                self.state = StateMsg::Error(format!(
+                                    "Tried to save the command history, but failed because {e}"
+                                ));
                             }
                         }
 
@@ -635,39 +1394,182 @@ impl Dune {
                             self.prompt.pop();
                             self.cursor.0 -= 1;
                         }
+
+                        ActionCommand::HistoryPrev => {
+                            if let Some(line) = self.history.prev(&self.prompt) {
+                                let line = line.to_owned();
+                                self.prompt_set(line);
+                            }
+                        }
+
+                        ActionCommand::HistoryNext => {
+                            if let Some(line) = self.history.next() {
+                                self.prompt_set(line);
+                            }
+                        }
+
+                        ActionCommand::Complete => {}
+
+                        ActionCommand::Leave => {
+                            self.history.reset();
+                            self.state = StateMsg::Ok;
+                            self.mode = Mode::Explorer;
+                        }
+
+                        ActionCommand::HistorySearch => {
+                            self.history.reset();
+                            self.history_search = Some(HistorySearch {
+                                found: None,
+                                typed: std::mem::take(&mut self.prompt),
+                            });
+                            self.prompt_open(PromptKind::HistorySearch, "".into());
+                        }
                     }
                 } else {
                     // It's just a char
-                    match evt {
-                        event::Event::Key(event::KeyEvent {
-                            code: event::KeyCode::Char(ch),
-                            kind: event::KeyEventKind::Press,
-                            ..
-                        }) => {
-                            self.prompt.push(ch);
-                            self.cursor.0 += 1;
-                        }
-                        _ => self.unknown_event(evt),
-                    }
+                    self.prompt_handle_char(evt);
                 }
             }
-            Mode::Explorer => {
-                if let Some(action) = self.key_bindings.get_explorer(&evt) {
+            Mode::Prompt(kind) => match self.key_bindings.get_command(&evt) {
+                Some(ActionCommand::Execute) => {
+                    let text = std::mem::take(&mut self.prompt);
+                    self.mode = kind.parent();
+                    self.state = StateMsg::Ok;
+                    self.prompt_submit(kind, &text);
+                }
+                Some(ActionCommand::PromptBackspace) => {
+                    if self.prompt.pop().is_some() {
+                        self.cursor.0 -= 1;
+                    }
+                    if kind == PromptKind::HistorySearch {
+                        // A shorter query can match newer lines.
+                        self.history_search_update(self.history.len());
+                    }
+                }
+                Some(ActionCommand::HistorySearch) if kind == PromptKind::HistorySearch => {
+                    let before = self.history_search.as_ref().and_then(|s| s.found);
+                    self.history_search_update(before.unwrap_or(self.history.len()));
+                }
+                Some(
+                    ActionCommand::HistoryPrev
+                    | ActionCommand::HistoryNext
+                    | ActionCommand::HistorySearch
+                    | ActionCommand::Complete
+                    | ActionCommand::Leave,
+                ) => {}
+                None => {
+                    self.prompt_handle_char(evt);
+                    if kind == PromptKind::HistorySearch {
+                        // The line found might still match the longer query.
+                        let found = self.history_search.as_ref().and_then(|s| s.found);
+                        self.history_search_update(found.map_or(self.history.len(), |i| i + 1));
+                    }
+                }
+            },
+            Mode::DiskUsage => {
+                let Some(du) = self.disk_usage.as_mut() else {
+                    return Ok(());
+                };
+                if let Some(action) = self.key_bindings.get_disk_usage(&evt) {
                     match action {
-                        ActionExplorer::NavLineUp => self.entries_scrolling_window.up(),
+                        ActionDiskUsage::NavLineUp => du.window.up(),
 
-                        ActionExplorer::NavLineDown => self.entries_scrolling_window.down(),
+                        ActionDiskUsage::NavLineDown => du.window.down(),
 
-                        ActionExplorer::NavHome => self.entries_scrolling_window.first(),
+                        ActionDiskUsage::NavHome => du.window.first(),
 
-                        ActionExplorer::NavEnd => self.entries_scrolling_window.last(),
+                        ActionDiskUsage::NavEnd => du.window.last(),
 
-                        ActionExplorer::DirEnter => {
-                            if let Some(entry) =
-                                self.entries.get(self.entries_scrolling_window.selected())
-                            {
-                                if !entry.is_dir() {
-                                    match open::that(entry.path()) {
+                        ActionDiskUsage::DirEnter => du.enter(),
+
+                        ActionDiskUsage::DirLeave => du.leave(),
+
+                        ActionDiskUsage::EntryDelete => {
+                            if let Some(selected) = du.selected() {
+                                let label = format!(
+                                    "Delete `{name}` ({size})? Type `y` to confirm:",
+                                    name = selected.name,
+                                    size = self.size_units.format(selected.size)
+                                );
+                                self.prompt_open(PromptKind::DiskUsageDelete, "".into());
+                                self.state = StateMsg::Info(label);
+                            }
+                        }
+
+                        ActionDiskUsage::Rescan => self.disk_usage_open(),
+                    }
+                }
+            }
+            Mode::Pager => {
+                let Some(pager) = self.pager.as_mut() else {
+                    return Ok(());
+                };
+                if let Some(action) = self.key_bindings.get_pager(&evt) {
+                    match action {
+                        ActionPager::NavLineUp => pager.scroll(-1),
+
+                        ActionPager::NavLineDown => pager.scroll(1),
+
+                        ActionPager::NavPageUp => pager.page_up(),
+
+                        ActionPager::NavPageDown => pager.page_down(),
+
+                        ActionPager::NavHome => pager.first(),
+
+                        ActionPager::NavEnd => pager.last(),
+
+                        ActionPager::SearchOpen => {
+                            self.prompt_open(PromptKind::PagerSearch, "".into())
+                        }
+
+                        ActionPager::SearchNext => pager.search_next(false),
+
+                        ActionPager::SearchPrev => pager.search_next(true),
+
+                        ActionPager::OutputPrev => {
+                            if let Some(idx) = self.output_idx.filter(|idx| *idx > 0) {
+                                self.output_open(idx - 1);
+                            }
+                        }
+
+                        ActionPager::OutputNext => {
+                            if let Some(idx) = self.output_idx {
+                                self.output_open(idx + 1);
+                            }
+                        }
+
+                        ActionPager::Close => {
+                            self.pager = None;
+                            self.output_idx = None;
+                            self.commands_list_open = false;
+                            self.mode = Mode::Explorer;
+                            self.state = StateMsg::Ok;
+                        }
+                    }
+                }
+            }
+            Mode::Explorer => {
+                if let Some(action) = self.key_bindings.get_explorer(&evt) {
+                    match action {
+                        ActionExplorer::NavLineUp => self.entries_scrolling_window.up(),
+
+                        ActionExplorer::NavLineDown => self.entries_scrolling_window.down(),
+
+                        ActionExplorer::NavHome => self.entries_scrolling_window.first(),
+
+                        ActionExplorer::NavEnd => self.entries_scrolling_window.last(),
+
+                        ActionExplorer::DirEnter => {
+                            if let Some(entry) =
+                                self.entries.get(self.entries_scrolling_window.selected())
+                            {
+                                if let Some(err) = entry.error() {
+                                    self.state = StateMsg::Error(format!(
+                                        "Could not load `{f}`: {err}",
+                                        f = entry.name()
+                                    ))
+                                } else if !entry.is_dir() {
+                                    match open::that(entry.path()) {
                                         Ok(()) => self.state = StateMsg::Ok,
                                         Err(e) => {
                                             self.state = StateMsg::Error(format!(
@@ -677,86 +1579,882 @@ impl Dune {
                                             ))
                                         }
                                     }
-                                } else if let Err(err) = cd(entry.name()) {
-                                    self.state = StateMsg::Error(format!(
-                                        "Tried to enter `{f}`, but failed because {err}",
-                                        f = entry.name()
-                                    ))
                                 } else {
-                                    self.update_entries()?;
-                                    self.entries_scrolling_window.first();
-                                    self.state = StateMsg::Ok;
+                                    let name = entry.name().to_owned();
+                                    let no_exec = entry.mode() & 0o111 == 0;
+                                    if let Err(err) = self.enter_dir(&name) {
+                                        // Readable but not executable directories can't be entered.
+                                        let hint = if no_exec
+                                            && err.kind() == io::ErrorKind::PermissionDenied
+                                        {
+                                            " (it's missing the execute permission)"
+                                        } else {
+                                            ""
+                                        };
+                                        self.state = StateMsg::Error(format!(
+                                            "Tried to enter `{name}`, but failed because {err}{hint}"
+                                        ))
+                                    } else {
+                                        self.entries_scrolling_window.first();
+                                        self.state = StateMsg::Ok;
+                                    }
                                 }
-                            } else {
-                                unreachable!("Selected line is out of bounds");
                             }
-                            // TODO: handle errors (file is not dir, no permissions...), print then on status bar?
+                            // If there is no entry the directory is empty, nothing to enter.
                         }
 
                         ActionExplorer::DirLeave => {
-                            cd("..")?;
-                            self.update_entries()?;
-                            self.entries_scrolling_window.first();
-                            self.state = StateMsg::Ok;
+                            if let Err(err) = self.enter_dir("..") {
+                                self.state = StateMsg::Error(format!(
+                                    "Tried to leave `{dir}`, but failed because {err}",
+                                    dir = self.curr_dir.path_str()
+                                ))
+                            } else {
+                                self.entries_scrolling_window.first();
+                                self.state = StateMsg::Ok;
+                            }
                         }
 
-                        ActionExplorer::EntriesUpdate => self.update_entries()?,
+                        ActionExplorer::EntriesUpdate => self.refresh_entries(),
 
                         ActionExplorer::EntriesSortToggle => {
-                            self.sorting = match self.sorting {
-                                Sorting::Default => Sorting::Date,
-                                Sorting::Date => Sorting::Name,
-                                Sorting::Name => Sorting::Type,
-                                Sorting::Type => Sorting::Default,
-                            };
+                            self.sorting.key = self.sorting.key.next();
                             self.sort_entries();
                             self.state = StateMsg::Info(format!("Sorting by {s}", s = self.sorting))
                         }
+
+                        ActionExplorer::SortReverseToggle => {
+                            self.sorting.reverse = !self.sorting.reverse;
+                            self.sort_entries();
+                            self.state = StateMsg::Info(format!("Sorting by {s}", s = self.sorting))
+                        }
+
+                        ActionExplorer::SortCaseToggle => {
+                            self.sorting.case_insensitive = !self.sorting.case_insensitive;
+                            self.sort_entries();
+                            self.state = StateMsg::Info(format!("Sorting by {s}", s = self.sorting))
+                        }
+
+                        ActionExplorer::SortDirsFirstToggle => {
+                            self.sorting.dirs_first = !self.sorting.dirs_first;
+                            self.sort_entries();
+                            self.state = StateMsg::Info(format!("Sorting by {s}", s = self.sorting))
+                        }
+
+                        ActionExplorer::SortHiddenLastToggle => {
+                            self.sorting.hidden_last = !self.sorting.hidden_last;
+                            self.sort_entries();
+                            self.state = StateMsg::Info(format!("Sorting by {s}", s = self.sorting))
+                        }
+
+                        ActionExplorer::EntryMarkToggle => {
+                            if let Some(entry) =
+                                self.entries.get(self.entries_scrolling_window.selected())
+                            {
+                                let path = entry.path().to_path_buf();
+                                if !self.marked.remove(&path) {
+                                    self.marked.insert(path);
+                                }
+                                self.entries_scrolling_window.down();
+                            }
+                        }
+
+                        ActionExplorer::ArchiveCreate => {
+                            let name = match self.selection().as_slice() {
+                                [] => None,
+                                [entry] => Some(entry.name().to_owned()),
+                                _ => Some(self.curr_dir.name().to_owned()),
+                            };
+                            if let Some(name) = name {
+                                self.prompt_open(
+                                    PromptKind::ArchiveName,
+                                    format!(
+                                        "{name}{ext}",
+                                        ext = archive::Format::TarGz.extension()
+                                    ),
+                                );
+                            } else {
+                                self.state = StateMsg::Error("Nothing to compress".into());
+                            }
+                        }
+
+                        ActionExplorer::DirSizesCalculate => {
+                            // Calculated again, the cached ones can miss changes deep in the tree.
+                            for entry in self.entries.iter() {
+                                self.dir_sizes.recalculate(entry);
+                            }
+                            self.state = StateMsg::Info("Calculating directory sizes".into());
+                        }
+
+                        ActionExplorer::DirSizesToggleAuto => {
+                            self.dir_sizes.auto = !self.dir_sizes.auto;
+                            if self.dir_sizes.auto {
+                                for entry in self.entries.iter() {
+                                    self.dir_sizes.request(entry);
+                                }
+                                self.state = StateMsg::Info(
+                                    "Calculating directory sizes automatically".into(),
+                                );
+                            } else {
+                                self.state = StateMsg::Info(
+                                    "Stopped calculating directory sizes automatically".into(),
+                                );
+                            }
+                        }
+
+                        ActionExplorer::DiskUsageOpen => self.disk_usage_open(),
+
+                        ActionExplorer::ColumnsEdit => {
+                            self.prompt_open(PromptKind::Columns, "".into())
+                        }
+
+                        ActionExplorer::LinkFollow => self.link_follow(),
+
+                        ActionExplorer::HiddenToggle => {
+                            self.show_hidden = !self.show_hidden;
+                            self.refresh_entries();
+                            self.state = StateMsg::Info(if self.show_hidden {
+                                "Showing hidden files".into()
+                            } else {
+                                "Hiding hidden files".into()
+                            });
+                        }
+
+                        ActionExplorer::IgnoredToggle => {
+                            self.hide_ignored = !self.hide_ignored;
+                            self.state = StateMsg::Info(if self.hide_ignored {
+                                "Hiding ignored files".into()
+                            } else {
+                                "Showing ignored files".into()
+                            });
+                            // After the message, so errors reading the rules are shown.
+                            self.refresh_entries();
+                        }
+
+                        ActionExplorer::GitStage => self.git_update_index(GitOperation::Stage),
+
+                        ActionExplorer::GitUnstage => self.git_update_index(GitOperation::Unstage),
+
+                        ActionExplorer::GitDiscard => {
+                            if self.git_status.repo.is_some() {
+                                self.prompt_open(PromptKind::GitDiscard, "".into())
+                            } else {
+                                self.state = StateMsg::Error(format!(
+                                    "`{dir}` is not in a git repository",
+                                    dir = self.curr_dir.path_str()
+                                ));
+                            }
+                        }
+
+                        ActionExplorer::GitDiff => self.git_diff(),
+
+                        ActionExplorer::CommandsList => {
+                            let mut list = self.commands_list();
+                            list.resize(self.panel_entries.height);
+                            self.pager = Some(list);
+                            self.output_idx = None;
+                            self.commands_list_open = true;
+                            self.mode = Mode::Pager;
+                        }
+
+                        ActionExplorer::OutputOpen => match self.outputs.len() {
+                            0 => {
+                                self.state =
+                                    StateMsg::Info("No command has printed anything yet".into())
+                            }
+                            len => self.output_open(len - 1),
+                        },
+
+                        ActionExplorer::TerminalFocus => self.pty_focus(),
+
+                        ActionExplorer::TerminalHide => {
+                            self.pty_visible = false;
+                            self.update_panels_size();
+                        }
+
+                        ActionExplorer::DetailsToggle => self.show_details = !self.show_details,
+
+                        ActionExplorer::ArchiveExtract => self.archive_extract(false),
+
+                        ActionExplorer::ArchiveExtractToDir => self.archive_extract(true),
                     }
                 }
             }
+            // Its keys were handled before the global ones.
+            Mode::Terminal => {}
         }
 
         Ok(())
     }
 
-    /// Sorts in place the entries
-    fn sort_entries(&mut self) {
-        self.entries.sort_by(|l, r| match self.sorting {
-            Sorting::Default => {
-                if l.name().starts_with('.') == r.name().starts_with('.') {
-                    if l.is_dir() == r.is_dir() {
-                        l.name().cmp(r.name())
-                    } else if l.is_dir() {
-                        Ordering::Less
-                    } else {
-                        Ordering::Greater
-                    }
-                } else if l.name().starts_with('.') {
-                    Ordering::Greater
+    fn prompt_handle_char(&mut self, evt: event::Event) {
+        match evt {
+            event::Event::Key(event::KeyEvent {
+                code: event::KeyCode::Char(ch),
+                kind: event::KeyEventKind::Press,
+                ..
+            }) => {
+                self.prompt.push(ch);
+                self.cursor.0 += 1;
+            }
+            _ => self.unknown_event(evt),
+        }
+    }
+
+    /// Replaces what is typed in the prompt, with the cursor at the end.
+    fn prompt_set(&mut self, text: String) {
+        self.cursor.0 = text.chars().count();
+        self.prompt = text;
+    }
+
+    /// Completes the last word of the prompt, listing what it could be if there is more than one option.
+    fn complete(&mut self) {
+        match completion::complete(&self.prompt, self.curr_dir.path()) {
+            Some(completion) => {
+                self.prompt_set(completion.line);
+                self.completions = completion.candidates;
+                self.state = StateMsg::Info("Command:".into());
+            }
+            None => self.state = StateMsg::Info("Command: nothing to complete with".into()),
+        }
+    }
+
+    /// Finds the newest line of the history with what is typed, older than line `before`.
+    /// If there is none, the line found before stays.
+    fn history_search_update(&mut self, before: usize) {
+        let Some(search) = self.history_search.as_mut() else {
+            return;
+        };
+        if self.prompt.is_empty() {
+            search.found = None;
+            self.state = StateMsg::Info(PromptKind::HistorySearch.label().into());
+            return;
+        }
+        self.state = match self.history.search(&self.prompt, before) {
+            Some((idx, line)) => {
+                search.found = Some(idx);
+                StateMsg::Info(format!("History search: {line}"))
+            }
+            None => StateMsg::Info(format!(
+                "History search: no {older}line has `{query}`",
+                older = if search.found.is_some() { "older " } else { "" },
+                query = self.prompt
+            )),
+        };
+    }
+
+    /// Asks the user for some text, starting with `text` already typed.
+    fn prompt_open(&mut self, kind: PromptKind, text: String) {
+        self.cursor = (text.chars().count(), self.vterm.lock().unwrap().height - 1);
+        self.prompt = text;
+        self.state = StateMsg::Info(kind.label().into());
+        self.mode = Mode::Prompt(kind);
+    }
+
+    fn prompt_submit(&mut self, kind: PromptKind, text: &str) {
+        match kind {
+            PromptKind::ArchiveName => self.archive_create(text),
+            PromptKind::Columns => self.columns_edit(text),
+            PromptKind::GitDiscard => {
+                if text.trim().eq_ignore_ascii_case("y") {
+                    self.git_update_index(GitOperation::Discard);
                 } else {
-                    Ordering::Less
+                    self.state = StateMsg::Info("Nothing was discarded".into());
                 }
             }
-            Sorting::Type => {
-                if l.is_dir() == r.is_dir() {
-                    l.name().cmp(r.name())
-                } else if l.is_dir() {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
+            PromptKind::PagerSearch => {
+                if let Some(pager) = self.pager.as_mut() {
+                    pager.search(text);
                 }
             }
-            Sorting::Date => l.last_modified().cmp(&r.last_modified()),
-            Sorting::Name => l.name().cmp(r.name()),
+            PromptKind::HistorySearch => {
+                let Some(search) = self.history_search.take() else {
+                    return;
+                };
+                let line = search.found.and_then(|idx| self.history.get(idx));
+                let line = line.map_or(search.typed, str::to_owned);
+                self.prompt_set(line);
+                self.state = StateMsg::Info("Command:".into());
+            }
+            PromptKind::DiskUsageDelete => {
+                if !text.trim().eq_ignore_ascii_case("y") {
+                    self.state = StateMsg::Info("Nothing was deleted".into());
+                    return;
+                }
+                let Some(du) = self.disk_usage.as_mut() else {
+                    return;
+                };
+                let Some(selected) = du.selected() else {
+                    return;
+                };
+                let (name, path, is_dir) = (
+                    selected.name.clone(),
+                    selected.path.clone(),
+                    selected.is_dir,
+                );
+
+                self.state = StateMsg::Info(format!("Deleting `{name}`"));
+                let id = self.jobs.spawn(format!("Deleting {name}"), {
+                    let path = path.clone();
+                    move |_| {
+                        let result = if is_dir {
+                            fs::remove_dir_all(&path)
+                        } else {
+                            fs::remove_file(&path)
+                        };
+                        result
+                            .map(|()| format!("Deleted `{name}`"))
+                            .map_err(|e| e.to_string())
+                    }
+                });
+                du.delete_started(id, path);
+            }
+        }
+    }
+
+    /// Stages, unstages or discards the changes of the selection in the background.
+    fn git_update_index(&mut self, operation: GitOperation) {
+        let Some(repo) = self.git_status.repo.as_ref() else {
+            self.state = StateMsg::Error(format!(
+                "`{dir}` is not in a git repository",
+                dir = self.curr_dir.path_str()
+            ));
+            return;
+        };
+
+        let root = repo.root.clone();
+        let paths = self
+            .selection()
+            .iter()
+            .map(|e| e.path().to_path_buf())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return;
+        }
+        self.marked.clear();
+
+        let (name, done) = match operation {
+            GitOperation::Stage => ("Staging", "Staged"),
+            GitOperation::Unstage => ("Unstaging", "Unstaged"),
+            GitOperation::Discard => ("Discarding", "Discarded"),
+        };
+        let count = paths.len();
+        self.jobs.spawn(format!("{name} {count} files"), move |_| {
+            let paths = paths.iter().map(|p| p.as_path()).collect::<Vec<_>>();
+            match operation {
+                GitOperation::Stage => git::git(&root, &["add"], &paths)?,
+                GitOperation::Unstage => git::unstage(&root, &paths)?,
+                // Only tracked files, untracked ones are never deleted.
+                GitOperation::Discard => git::git(&root, &["restore", "--worktree"], &paths)?,
+            };
+            Ok(format!("{done} {count} files"))
         });
     }
 
+    /// Starts reading the changes of the selected file, to show them in the pager.
+    fn git_diff(&mut self) {
+        let Some(entry) = self.entries.get(self.entries_scrolling_window.selected()) else {
+            return;
+        };
+        let Some(repo) = self.git_status.repo.as_ref() else {
+            self.state = StateMsg::Error(format!(
+                "`{dir}` is not in a git repository",
+                dir = self.curr_dir.path_str()
+            ));
+            return;
+        };
+
+        let untracked = repo.get(entry.path()) == Some(git::FileStatus::Untracked);
+        self.git_diff
+            .request(repo.root.clone(), entry.path().to_path_buf(), untracked);
+        self.state = StateMsg::Info(format!("Reading the changes of `{f}`", f = entry.name()));
+    }
+
+    fn poll_git_diff(&mut self) {
+        let Some((path, diff)) = self.git_diff.poll() else {
+            return;
+        };
+        let name = path
+            .file_name()
+            .map_or_else(|| path.to_string_lossy(), |n| n.to_string_lossy());
+        match diff {
+            Ok(diff) if diff.is_empty() => {
+                self.state = StateMsg::Info(format!("`{name}` has no changes"));
+            }
+            Ok(diff) => {
+                let mut pager =
+                    pager::Pager::diff(format!("Diff of {p}", p = path.to_string_lossy()), &diff);
+                pager.resize(self.panel_entries.height);
+                self.pager = Some(pager);
+                self.mode = Mode::Pager;
+                self.state = StateMsg::Ok;
+            }
+            Err(e) => {
+                self.state = StateMsg::Error(format!(
+                    "Tried to show the changes of `{name}`, but failed because {e}"
+                ));
+            }
+        }
+    }
+
+    /// Goes to the directory where the target of the selected link is, and selects it.
+    fn link_follow(&mut self) {
+        let Some(entry) = self.entries.get(self.entries_scrolling_window.selected()) else {
+            return;
+        };
+        let Some(target) = entry.link_target_abs() else {
+            self.state = StateMsg::Error(format!("`{f}` is not a link", f = entry.name()));
+            return;
+        };
+
+        let (dir, target_name) = match (target.parent(), target.file_name()) {
+            (Some(dir), Some(name)) => (dir, Some(name)),
+            // Targets like `..` or `/` have no name to select, they are directories to go into.
+            _ => (target.as_path(), None),
+        };
+        if let Err(err) = self.enter_dir(dir) {
+            self.state = StateMsg::Error(format!(
+                "Tried to go to `{dir}`, but failed because {err}",
+                dir = dir.to_string_lossy()
+            ));
+            return;
+        }
+        let Some(target_name) = target_name else {
+            self.state = StateMsg::Ok;
+            return;
+        };
+
+        if let Some(idx) = self
+            .entries
+            .iter()
+            .position(|e| e.path().file_name() == Some(target_name))
+        {
+            self.entries_scrolling_window.select(idx);
+            self.state = StateMsg::Ok;
+        } else {
+            self.state = StateMsg::Error(format!(
+                "`{target}` doesn't exist",
+                target = target.to_string_lossy()
+            ));
+        }
+    }
+
+    /// Changes the columns of the entries table, see `columns::command`.
+    fn columns_edit(&mut self, text: &str) {
+        self.state = match columns::command(&mut self.columns, text, self.vterm.clone()) {
+            Ok(msg) => StateMsg::Info(msg),
+            Err(e) => StateMsg::Error(format!(
+                "Tried to change the columns, but failed because {e}"
+            )),
+        };
+        self.update_panels_size();
+    }
+
+    /// Scans the current directory and shows the disk usage view.
+    fn disk_usage_open(&mut self) {
+        self.disk_usage = Some(disk_usage::DiskUsage::scan(
+            self.curr_dir.path().to_path_buf(),
+            self.panel_entries.height,
+        ));
+        self.mode = Mode::DiskUsage;
+        self.state = StateMsg::Info(format!(
+            "Scanning `{path}`",
+            path = self.curr_dir.path_str()
+        ));
+    }
+
+    /// Entries the actions should work on.
+    /// The marked ones, or the selected one if nothing is marked.
+    fn selection(&self) -> Vec<&file_info::FileInfo> {
+        if self.marked.is_empty() {
+            self.entries
+                .get(self.entries_scrolling_window.selected())
+                .into_iter()
+                .collect()
+        } else {
+            self.entries
+                .iter()
+                .filter(|e| self.marked.contains(e.path()))
+                .collect()
+        }
+    }
+
+    /// Runs a command attached to the terminal, with the interface suspended until it ends.
+    fn command_run_attached(&mut self, exec: &mut process::Command) -> io::Result<()> {
+        VTerm::suspend()?;
+        let result = shell::run_attached(exec);
+        if self.command_settings.wait_for_key {
+            let status = match &result {
+                Ok(output) => &output.status,
+                Err(e) => e,
+            };
+            VTerm::wait_for_key(&format!("[{status}] Press any key to continue"))?;
+        }
+        self.vterm.lock().unwrap().resume()?;
+        self.update_panels_size();
+
+        self.state = match result {
+            Ok(output) => {
+                let report = format!(
+                    "{command}: {status}",
+                    command = output.command,
+                    status = output.status
+                );
+                if output.success {
+                    StateMsg::Info(report)
+                } else {
+                    StateMsg::Error(report)
+                }
+            }
+            Err(e) => StateMsg::Error(e),
+        };
+        Ok(())
+    }
+
+    /// Starts a command in the background, what it prints comes with `poll_commands`.
+    fn command_spawn(&mut self, exec: &mut process::Command) {
+        self.state = match self.commands.spawn(exec) {
+            Ok(id) => match self.commands.get(id) {
+                Some(command) => {
+                    let report = format!(
+                        "{command}: running, pid {pid}",
+                        command = command.command,
+                        pid = command.pid
+                    );
+                    self.output_auto_open = Some(id);
+                    self.state_command = Some((id, report.clone()));
+                    StateMsg::Info(report)
+                }
+                None => StateMsg::Ok,
+            },
+            Err(e) => StateMsg::Error(e),
+        };
+    }
+
+    /// The command Ctrl-C interrupts: the one whose output the pager or the state panel shows, if it's running.
+    fn command_foreground(&self) -> Option<&commands::Command> {
+        let in_pager = self
+            .output_idx
+            .filter(|_| self.pager.is_some())
+            .and_then(|idx| self.outputs.get(idx))
+            .map(|(id, _)| *id);
+        let in_state = self
+            .state_command
+            .as_ref()
+            .filter(|(_, report)| matches!(&self.state, StateMsg::Info(text) if text == report))
+            .map(|(id, _)| *id);
+        in_pager
+            .or(in_state)
+            .and_then(|id| self.commands.get(id))
+            .filter(|command| command.is_running())
+    }
+
+    /// Shows the embedded terminal and types into it, starting its shell if needed.
+    fn pty_focus(&mut self) {
+        if self.pty.is_none() {
+            // The right size is set along with the panels.
+            match pty::Pty::spawn(self.curr_dir.path(), 24, 80) {
+                Ok(pty) => self.pty = Some(pty),
+                Err(e) => {
+                    self.state = StateMsg::Error(format!(
+                        "Tried to start the terminal, but failed because {e}"
+                    ));
+                    return;
+                }
+            }
+        }
+        self.pty_visible = true;
+        self.mode = Mode::Terminal;
+        self.state = StateMsg::Ok;
+        self.update_panels_size();
+    }
+
+    fn poll_pty(&mut self) {
+        let Some(status) = self.pty.as_mut().and_then(|pty| pty.poll()) else {
+            return;
+        };
+        self.pty = None;
+        self.pty_visible = false;
+        if self.mode == Mode::Terminal {
+            self.mode = Mode::Explorer;
+        }
+        self.state = StateMsg::Info(format!("The terminal's shell ended: {status}"));
+        self.update_panels_size();
+    }
+
+    fn poll_commands(&mut self) {
+        let updates = self.commands.poll();
+        if updates.is_empty() {
+            return;
+        }
+
+        for update in updates {
+            match update {
+                commands::Update::Output { id, text } => self.output_push(id, &text),
+                commands::Update::Finished { id } => self.command_finished(id),
+            }
+        }
+        // Updated in place, not to lose the scroll nor the search.
+        if self.commands_list_open {
+            let text = self.commands_list_text();
+            if let Some(pager) = self.pager.as_mut() {
+                pager.set_text(&text);
+            }
+        }
+    }
+
+    /// Adds what a command printed to its output.
+    /// The last line is shown in the state panel, and the pager is opened once there is more than one.
+    fn output_push(&mut self, id: commands::CommandId, text: &str) {
+        let Some(command) = self.commands.get(id) else {
+            return;
+        };
+        let idx = match self
+            .outputs
+            .iter()
+            .position(|(output_id, _)| *output_id == id)
+        {
+            Some(idx) => idx,
+            None => {
+                if self.outputs.len() == OUTPUTS_MAX {
+                    self.outputs.remove(0);
+                    self.output_idx = self.output_idx.and_then(|idx| idx.checked_sub(1));
+                }
+                let output = pager::Pager::ansi(output_title(command), "");
+                self.outputs.push((id, output));
+                self.outputs.len() - 1
+            }
+        };
+        let report_prefix = command.command.clone();
+
+        let output = &mut self.outputs[idx].1;
+        let was_one_line = output.line_count() <= 1;
+        output.push_ansi(text);
+        output.keep_last(OUTPUT_LINES_MAX);
+        let line_count = output.line_count();
+        let last_line = line_count
+            .checked_sub(1)
+            .map(|last| output.line_text(last).to_owned())
+            .unwrap_or_default();
+        if self.output_idx == Some(idx) {
+            if let Some(pager) = self.pager.as_mut() {
+                pager.push_ansi(text);
+                pager.keep_last(OUTPUT_LINES_MAX);
+            }
+        }
+
+        // Not to get in the way of the other modes.
+        if matches!(self.mode, Mode::Explorer | Mode::Command) {
+            let report = format!("{report_prefix}: {last_line}");
+            self.state = StateMsg::Info(report.clone());
+            self.state_command = Some((id, report));
+            // Not while a new line is being typed either.
+            let is_typing = self.mode == Mode::Command && !self.prompt.is_empty();
+            if was_one_line && line_count > 1 && self.output_auto_open == Some(id) && !is_typing {
+                self.output_open(idx);
+            }
+        }
+    }
+
+    /// Reports how a command went, and lists the directory again since it could have changed it.
+    fn command_finished(&mut self, id: commands::CommandId) {
+        let Some(command) = self.commands.get(id) else {
+            return;
+        };
+        let title = output_title(command);
+        let mut report = format!(
+            "{command}: {status}",
+            command = command.command,
+            status = command.describe_status()
+        );
+
+        let output = self
+            .outputs
+            .iter_mut()
+            .enumerate()
+            .find(|(_, (output_id, _))| *output_id == id);
+        if let Some((idx, (_, output))) = output {
+            if output.line_count() == 1 {
+                report.push_str(&format!(": {line}", line = output.line_text(0)));
+            }
+            output.title = title.clone();
+            if self.output_idx == Some(idx) {
+                if let Some(pager) = self.pager.as_mut() {
+                    pager.title = title;
+                }
+            }
+        }
+        if command.invalid_utf8 {
+            report.push_str(": output is not valid UTF-8");
+        }
+
+        // Prompts show what they are asking for in the state panel.
+        if !matches!(self.mode, Mode::Prompt(_)) {
+            self.state = if command.success() {
+                StateMsg::Info(report)
+            } else {
+                StateMsg::Error(report)
+            };
+        }
+        self.refresh_entries();
+    }
+
+    /// The commands started from command mode with their PIDs and statuses, the newest first.
+    fn commands_list(&self) -> pager::Pager {
+        pager::Pager::new("Commands".into(), &self.commands_list_text())
+    }
+
+    fn commands_list_text(&self) -> String {
+        let mut text = format!(
+            "{pid:<8}  {status:<24}  COMMAND\n",
+            pid = "PID",
+            status = "STATUS"
+        );
+        for command in self.commands.list().iter().rev() {
+            text.push_str(&format!(
+                "{pid:<8}  {status:<24}  {command}\n",
+                pid = command.pid,
+                status = command.describe_status(),
+                command = command.command
+            ));
+        }
+        text
+    }
+
+    /// Shows the command output at `idx` of `outputs` in the pager.
+    fn output_open(&mut self, idx: usize) {
+        let Some((_, output)) = self.outputs.get(idx) else {
+            return;
+        };
+        let mut pager = output.clone();
+        pager.resize(self.panel_entries.height);
+        self.pager = Some(pager);
+        self.output_idx = Some(idx);
+        self.commands_list_open = false;
+        self.mode = Mode::Pager;
+    }
+
+    /// Values of `%f`, `%F`, `%d`, `%n` and `%e` in command mode.
+    fn placeholders(&self) -> shell::Placeholders<'_> {
+        shell::Placeholders {
+            dir: self.curr_dir.path_str(),
+            selected: self
+                .entries
+                .get(self.entries_scrolling_window.selected())
+                .map(|e| e.name()),
+            marked: self.selection().into_iter().map(|e| e.name()).collect(),
+        }
+    }
+
+    /// Compresses the selection into a new archive named `name` in the current directory.
+    fn archive_create(&mut self, name: &str) {
+        let dest = self.curr_dir.path().join(name);
+        let Some(format) = archive::Format::from_path(&dest) else {
+            self.state = StateMsg::Error(format!(
+                "Unknown archive format for `{name}`, use .tar.gz, .tar.zst or .zip"
+            ));
+            return;
+        };
+        if dest.exists() {
+            self.state = StateMsg::Error(format!("`{name}` already exists"));
+            return;
+        }
+
+        let sources = self
+            .selection()
+            .iter()
+            .map(|e| e.path().to_path_buf())
+            .collect::<Vec<_>>();
+        let base = self.curr_dir.path().to_path_buf();
+        self.marked.clear();
+
+        let name = name.to_owned();
+        self.state = StateMsg::Info(format!("Compressing into `{name}` ({format})"));
+        self.jobs
+            .spawn(format!("Compressing {name}"), move |progress| {
+                archive::compress(&sources, &base, &dest, format, |done, total| {
+                    progress.report(done, total)
+                })
+                .map(|()| format!("Compressed into `{name}`"))
+                .map_err(|e| {
+                    if e.kind() != io::ErrorKind::AlreadyExists {
+                        // Don't leave a half written archive behind.
+                        let _ = fs::remove_file(&dest);
+                    }
+                    e.to_string()
+                })
+            });
+    }
+
+    /// Extracts the selected archive into the current directory,
+    /// or into a folder named after the archive if `into_subfolder`.
+    fn archive_extract(&mut self, into_subfolder: bool) {
+        let Some(entry) = self.entries.get(self.entries_scrolling_window.selected()) else {
+            return;
+        };
+        let name = entry.name().to_owned();
+        let archive = entry.path().to_path_buf();
+        let Some(stem) = archive::stem(&archive) else {
+            self.state = StateMsg::Error(format!(
+                "`{name}` is not a supported archive (.tar.gz, .tar.zst or .zip)"
+            ));
+            return;
+        };
+
+        let dest = if into_subfolder {
+            let dest = self.curr_dir.path().join(stem);
+            if dest.exists() {
+                self.state = StateMsg::Error(format!("`{stem}` already exists"));
+                return;
+            }
+            dest
+        } else {
+            self.curr_dir.path().to_path_buf()
+        };
+
+        self.state = StateMsg::Info(format!("Extracting `{name}`"));
+        self.jobs
+            .spawn(format!("Extracting {name}"), move |progress| {
+                archive::extract(&archive, &dest, |done, total| progress.report(done, total))
+                    .map(|()| format!("Extracted `{name}`"))
+                    .map_err(|e| e.to_string())
+            });
+    }
+
+    /// Sorts the entries, keeping the same entry selected.
+    fn sort_entries(&mut self) {
+        let selected = self
+            .entries
+            .get(self.entries_scrolling_window.selected())
+            .map(|e| e.path().to_path_buf());
+
+        self.sorting.sort(&mut self.entries, &mut self.users);
+
+        if let Some(idx) =
+            selected.and_then(|path| self.entries.iter().position(|e| e.path() == path))
+        {
+            self.entries_scrolling_window.select(idx);
+        }
+    }
+
     fn unknown_event(&mut self, _evt: event::Event) {
         // For now, don't do anything...
     }
 }
 
+/// Eg.: ``Output of `make` (running, pid 1234)``
+fn output_title(command: &commands::Command) -> String {
+    let status = if command.is_running() {
+        format!("running, pid {pid}", pid = command.pid)
+    } else {
+        command.describe_status()
+    };
+    format!(
+        "Output of `{command}` ({status})",
+        command = command.command
+    )
+}
+
 fn cd<P: AsRef<path::Path>>(dir: P) -> io::Result<()> {
     env::set_current_dir(dir)
 }
@@ -770,6 +2468,7 @@ fn main() -> process::ExitCode {
     let mut app = Dune::new(
         Arc::new(Mutex::new(VTerm::new())),
         key_bindings::new(),
+        config::Config::load(),
         starting_dir,
     );
 
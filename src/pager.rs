@@ -0,0 +1,602 @@
+use std::ops;
+
+use crossterm::style::{Attribute, Color, ContentStyle, Stylize};
+
+/// A line of text, with the style of each part of it.
+#[derive(Clone)]
+struct Line {
+    text: String,
+    /// Byte index where each style starts, the first one at 0.
+    styles: Vec<(usize, ContentStyle)>,
+}
+
+impl Line {
+    fn plain(text: String) -> Self {
+        Self {
+            text,
+            styles: vec![(0, ContentStyle::new())],
+        }
+    }
+}
+
+/// A scrollable, read only view of some text.
+#[derive(Clone)]
+pub struct Pager {
+    pub title: String,
+    lines: Vec<Line>,
+    /// First line shown.
+    top: usize,
+    /// Amount of lines that fit on the screen.
+    height: usize,
+    search: Option<Search>,
+    /// Where the ANSI escape sequences left the style, for the text pushed next.
+    ansi_style: ContentStyle,
+    /// The last line while it doesn't end with a newline, with the style it started with.
+    /// It's parsed again with what continues it, as it could have been cut in the middle of an escape sequence.
+    open_line: Option<(String, ContentStyle)>,
+}
+
+#[derive(Clone)]
+struct Search {
+    query: String,
+    /// Only when the query has no uppercase letters.
+    case_insensitive: bool,
+    /// Lines with at least one match.
+    lines: Vec<usize>,
+    /// Index in `lines` of the match we moved to.
+    current: Option<usize>,
+}
+
+impl Search {
+    /// Byte ranges of the matches in `text`.
+    fn matches(&self, text: &str) -> Vec<ops::Range<usize>> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+        // ASCII lowercase keeps the byte indexes of the original text.
+        let haystack = if self.case_insensitive {
+            text.to_ascii_lowercase()
+        } else {
+            text.to_owned()
+        };
+        haystack
+            .match_indices(&self.query)
+            .map(|(start, found)| start..start + found.len())
+            .collect()
+    }
+}
+
+impl Pager {
+    pub fn new(title: String, text: &str) -> Self {
+        let lines = text
+            .lines()
+            .map(|line| Line::plain(expand_tabs(line)))
+            .collect();
+        Self::with_lines(title, lines)
+    }
+
+    /// Replaces the text with `text`, like `new`, staying where the view is and keeping the search.
+    pub fn set_text(&mut self, text: &str) {
+        self.lines = text
+            .lines()
+            .map(|line| Line::plain(expand_tabs(line)))
+            .collect();
+        self.open_line = None;
+        if let Some(search) = self.search.as_mut() {
+            search.lines = (0..self.lines.len())
+                .filter(|idx| !search.matches(&self.lines[*idx].text).is_empty())
+                .collect();
+            search.current = search
+                .current
+                .filter(|current| *current < search.lines.len());
+        }
+        self.scroll(0);
+    }
+
+    /// Like `new`, with the lines of an unified diff colored.
+    pub fn diff(title: String, text: &str) -> Self {
+        let mut pager = Self::new(title, text);
+        for line in pager.lines.iter_mut() {
+            let text = &line.text;
+            let style = &mut line.styles[0].1;
+            *style = if text.starts_with("+++") || text.starts_with("---") {
+                style.bold()
+            } else if text.starts_with('+') {
+                style.green()
+            } else if text.starts_with('-') {
+                style.red()
+            } else if text.starts_with("@@") {
+                style.cyan()
+            } else if text.starts_with("diff ") || text.starts_with("index ") {
+                style.bold()
+            } else {
+                *style
+            };
+        }
+        pager
+    }
+
+    /// Like `new`, styling the text with its ANSI escape sequences, like the output of `ls --color=always`.
+    /// Colors and attributes carry over to the next lines until reset, other sequences are dropped.
+    pub fn ansi(title: String, text: &str) -> Self {
+        let mut pager = Self::with_lines(title, Vec::new());
+        pager.push_ansi(text);
+        pager
+    }
+
+    /// Adds `text` with ANSI escape sequences after the last line, for output that is still coming.
+    /// If the last line didn't end with a newline, `text` continues it.
+    /// If the view was at the end it stays there.
+    pub fn push_ansi(&mut self, text: &str) {
+        let at_end = self.top + self.height >= self.lines.len();
+        let mut text = text.to_owned();
+        if let Some((open_line, style)) = self.open_line.take() {
+            self.lines.pop();
+            if let Some(search) = self.search.as_mut() {
+                if search.lines.last() == Some(&self.lines.len()) {
+                    search.lines.pop();
+                    search.current = search
+                        .current
+                        .filter(|current| *current < search.lines.len());
+                }
+            }
+            self.ansi_style = style;
+            text.insert_str(0, &open_line);
+        }
+
+        for line in text.split_inclusive('\n') {
+            let line = match line.strip_suffix('\n') {
+                Some(line) => line,
+                None => {
+                    self.open_line = Some((line.to_owned(), self.ansi_style));
+                    line
+                }
+            };
+            let line = parse_ansi(line, &mut self.ansi_style);
+            if let Some(search) = self.search.as_mut() {
+                if !search.matches(&line.text).is_empty() {
+                    search.lines.push(self.lines.len());
+                }
+            }
+            self.lines.push(line);
+        }
+        if at_end {
+            self.last();
+        }
+    }
+
+    /// Drops the first lines, so there are no more than `max`.
+    pub fn keep_last(&mut self, max: usize) {
+        let extra = self.lines.len().saturating_sub(max);
+        if extra == 0 {
+            return;
+        }
+        self.lines.drain(..extra);
+        self.top = self.top.saturating_sub(extra);
+        if let Some(search) = self.search.as_mut() {
+            let dropped = search
+                .lines
+                .iter()
+                .take_while(|line| **line < extra)
+                .count();
+            search.lines.drain(..dropped);
+            for line in search.lines.iter_mut() {
+                *line -= extra;
+            }
+            search.current = search
+                .current
+                .and_then(|current| current.checked_sub(dropped));
+        }
+    }
+
+    fn with_lines(title: String, lines: Vec<Line>) -> Self {
+        Self {
+            title,
+            lines,
+            top: 0,
+            height: 0,
+            search: None,
+            ansi_style: ContentStyle::new(),
+            open_line: None,
+        }
+    }
+
+    pub fn resize(&mut self, height: usize) {
+        self.height = height;
+        self.scroll(0);
+    }
+
+    /// Indexes of the lines that fit on the screen.
+    pub fn visible(&self) -> ops::Range<usize> {
+        self.top..(self.top + self.height).min(self.lines.len())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Text of the line at `idx`, without styles.
+    pub fn line_text(&self, idx: usize) -> &str {
+        &self.lines[idx].text
+    }
+
+    /// The parts of the line at `idx` with their style, matches of the search highlighted.
+    pub fn spans(&self, idx: usize) -> Vec<(&str, ContentStyle)> {
+        let line = &self.lines[idx];
+        let matches = self
+            .search
+            .as_ref()
+            .map(|s| s.matches(&line.text))
+            .unwrap_or_default();
+
+        // Split wherever a style starts, or a match starts or ends.
+        let mut bounds = line
+            .styles
+            .iter()
+            .map(|(start, _)| *start)
+            .chain(matches.iter().flat_map(|m| [m.start, m.end]))
+            .chain([line.text.len()])
+            .collect::<Vec<_>>();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        bounds
+            .windows(2)
+            .map(|bound| {
+                let (start, end) = (bound[0], bound[1]);
+                let style = line
+                    .styles
+                    .iter()
+                    .rev()
+                    .find(|(style_start, _)| *style_start <= start)
+                    .map_or_else(ContentStyle::new, |(_, style)| *style);
+                let style = if matches.iter().any(|m| m.contains(&start)) {
+                    style.black().on_yellow()
+                } else {
+                    style
+                };
+                (&line.text[start..end], style)
+            })
+            .collect()
+    }
+
+    /// Moves the view by `lines`, up if negative, without going past the end.
+    pub fn scroll(&mut self, lines: isize) {
+        let max_top = self.lines.len().saturating_sub(self.height);
+        self.top = self.top.saturating_add_signed(lines).min(max_top);
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll(self.height.max(1) as isize);
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll(-(self.height.max(1) as isize));
+    }
+
+    pub fn first(&mut self) {
+        self.top = 0;
+    }
+
+    pub fn last(&mut self) {
+        self.top = self.lines.len().saturating_sub(self.height);
+    }
+
+    /// Highlights `query` and moves to its first match from the top of the view.
+    /// It's case insensitive unless it has uppercase letters, an empty one clears the search.
+    pub fn search(&mut self, query: &str) {
+        if query.is_empty() {
+            self.search = None;
+            return;
+        }
+
+        let case_insensitive = !query.chars().any(char::is_uppercase);
+        let mut search = Search {
+            query: query.to_owned(),
+            case_insensitive,
+            lines: Vec::new(),
+            current: None,
+        };
+        search.lines = (0..self.lines.len())
+            .filter(|idx| !search.matches(&self.lines[*idx].text).is_empty())
+            .collect();
+        // The first match from where we are, or the first one if they are all above.
+        search.current =
+            search
+                .lines
+                .iter()
+                .position(|line| *line >= self.top)
+                .or(if search.lines.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                });
+        self.search = Some(search);
+        self.goto_match();
+    }
+
+    /// Moves to the next match, or the previous one if `backwards`, wrapping around.
+    pub fn search_next(&mut self, backwards: bool) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let count = search.lines.len();
+        if count == 0 {
+            return;
+        }
+        search.current = Some(match search.current {
+            Some(current) if backwards => (current + count - 1) % count,
+            Some(current) => (current + 1) % count,
+            None => 0,
+        });
+        self.goto_match();
+    }
+
+    /// Eg.: `/main (2 of 7 matching lines)`, `None` if not searching.
+    pub fn search_status(&self) -> Option<String> {
+        let search = self.search.as_ref()?;
+        Some(match search.current {
+            Some(current) => format!(
+                "/{query} ({n} of {count} matching lines)",
+                query = search.query,
+                n = current + 1,
+                count = search.lines.len()
+            ),
+            None if search.lines.is_empty() => {
+                format!("/{query} (no matches)", query = search.query)
+            }
+            None => format!(
+                "/{query} ({count} matching lines)",
+                query = search.query,
+                count = search.lines.len()
+            ),
+        })
+    }
+
+    fn goto_match(&mut self) {
+        let Some(line) = self.search.as_ref().and_then(|s| Some(s.lines[s.current?])) else {
+            return;
+        };
+        self.top = line;
+        self.scroll(0);
+    }
+}
+
+/// Tabs would be drawn as a single cell, so they are replaced with spaces up to the next tab stop.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for ch in line.chars() {
+        push_char(&mut expanded, ch);
+    }
+    expanded
+}
+
+fn push_char(text: &mut String, ch: char) {
+    const TAB_WIDTH: usize = 8;
+    match ch {
+        '\t' => {
+            let spaces = TAB_WIDTH - text.chars().count() % TAB_WIDTH;
+            text.extend(std::iter::repeat_n(' ', spaces));
+        }
+        // Carriage returns of `\r\n` endings, or progress bars, would move the cursor.
+        '\r' => {}
+        ch => text.push(ch),
+    }
+}
+
+/// Strips the escape sequences of `line`, applying the SGR ones (`ESC [ ... m`) to `style`.
+fn parse_ansi(line: &str, style: &mut ContentStyle) -> Line {
+    let mut text = String::with_capacity(line.len());
+    let mut styles = vec![(0, *style)];
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            push_char(&mut text, ch);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: parameters and intermediate bytes, up to a final byte in `@`..=`~`.
+            Some('[') => {
+                let mut params = String::new();
+                let mut last = None;
+                for ch in chars.by_ref() {
+                    if ('@'..='~').contains(&ch) {
+                        last = Some(ch);
+                        break;
+                    }
+                    params.push(ch);
+                }
+                if last != Some('m') {
+                    continue;
+                }
+                apply_sgr(&params, style);
+                // The last style wins if there was no text since the previous one.
+                if styles.last().is_some_and(|(start, _)| *start == text.len()) {
+                    styles.pop();
+                }
+                styles.push((text.len(), *style));
+            }
+            // OSC, like hyperlinks: up to a BEL or `ESC \`.
+            Some(']') => {
+                while let Some(ch) = chars.next() {
+                    if ch == '\x07' || (ch == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            // Two character sequences, like `ESC =`.
+            _ => {}
+        }
+    }
+
+    Line { text, styles }
+}
+
+/// Applies the parameters of a SGR sequence, eg.: `1;31` is bold and red.
+fn apply_sgr(params: &str, style: &mut ContentStyle) {
+    let mut params = params
+        .split([';', ':'])
+        .map(|p| p.parse::<u8>().unwrap_or(0));
+    // `ESC [ m` is a reset too.
+    let mut next = Some(params.next().unwrap_or(0));
+    while let Some(param) = next {
+        match param {
+            0 => *style = ContentStyle::new(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            5 => style.attributes.set(Attribute::SlowBlink),
+            7 => style.attributes.set(Attribute::Reverse),
+            8 => style.attributes.set(Attribute::Hidden),
+            9 => style.attributes.set(Attribute::CrossedOut),
+            22 => {
+                style.attributes.unset(Attribute::Bold);
+                style.attributes.unset(Attribute::Dim);
+            }
+            23 => style.attributes.unset(Attribute::Italic),
+            24 => style.attributes.unset(Attribute::Underlined),
+            25 => style.attributes.unset(Attribute::SlowBlink),
+            27 => style.attributes.unset(Attribute::Reverse),
+            28 => style.attributes.unset(Attribute::Hidden),
+            29 => style.attributes.unset(Attribute::CrossedOut),
+            30..=37 => style.foreground_color = Some(Color::AnsiValue(param - 30)),
+            38 => style.foreground_color = extended_color(&mut params),
+            39 => style.foreground_color = None,
+            40..=47 => style.background_color = Some(Color::AnsiValue(param - 40)),
+            48 => style.background_color = extended_color(&mut params),
+            49 => style.background_color = None,
+            90..=97 => style.foreground_color = Some(Color::AnsiValue(param - 90 + 8)),
+            100..=107 => style.background_color = Some(Color::AnsiValue(param - 100 + 8)),
+            _ => {}
+        }
+        next = params.next();
+    }
+}
+
+/// The color after a `38` or `48`: `5;n` from the 256 colors palette, or `2;r;g;b`.
+fn extended_color(params: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(Color::AnsiValue(params.next()?)),
+        2 => Some(Color::Rgb {
+            r: params.next()?,
+            g: params.next()?,
+            b: params.next()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red() -> ContentStyle {
+        ContentStyle::new().with(Color::AnsiValue(1))
+    }
+
+    #[test]
+    fn parse_ansi_applies_colors() {
+        let mut style = ContentStyle::new();
+        let line = parse_ansi("a \x1b[31mred\x1b[0m b", &mut style);
+        assert_eq!(line.text, "a red b");
+        assert_eq!(
+            line.styles,
+            [
+                (0, ContentStyle::new()),
+                (2, red()),
+                (5, ContentStyle::new())
+            ]
+        );
+        assert_eq!(style, ContentStyle::new());
+    }
+
+    #[test]
+    fn parse_ansi_carries_the_style_over() {
+        let mut style = ContentStyle::new();
+        parse_ansi("\x1b[1;31mbold", &mut style);
+        let line = parse_ansi("still", &mut style);
+        assert_eq!(line.styles, [(0, red().bold())]);
+    }
+
+    #[test]
+    fn parse_ansi_extended_colors() {
+        let mut style = ContentStyle::new();
+        parse_ansi("\x1b[38;5;208;48;2;1;2;3m", &mut style);
+        assert_eq!(style.foreground_color, Some(Color::AnsiValue(208)));
+        assert_eq!(
+            style.background_color,
+            Some(Color::Rgb { r: 1, g: 2, b: 3 })
+        );
+    }
+
+    #[test]
+    fn parse_ansi_drops_other_sequences() {
+        let mut style = ContentStyle::new();
+        let line = parse_ansi(
+            "\x1b[2K\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07\x1b=\tend\r",
+            &mut style,
+        );
+        assert_eq!(line.text, "link    end");
+        assert_eq!(line.styles, [(0, ContentStyle::new())]);
+    }
+
+    #[test]
+    fn parse_ansi_keeps_the_last_of_styles_in_a_row() {
+        let mut style = ContentStyle::new();
+        let line = parse_ansi("\x1b[32m\x1b[31mred", &mut style);
+        assert_eq!(line.styles, [(0, red())]);
+    }
+
+    fn texts(pager: &Pager) -> Vec<&str> {
+        (0..pager.line_count())
+            .map(|idx| pager.line_text(idx))
+            .collect()
+    }
+
+    #[test]
+    fn push_ansi_continues_the_last_line() {
+        let mut pager = Pager::ansi("output".into(), "");
+        pager.push_ansi("a\nb");
+        assert_eq!(texts(&pager), ["a", "b"]);
+        pager.push_ansi("c\x1b[3");
+        assert_eq!(texts(&pager), ["a", "bc"]);
+        // The escape sequence was cut in half.
+        pager.push_ansi("1md\n");
+        assert_eq!(texts(&pager), ["a", "bcd"]);
+        assert_eq!(
+            pager.lines[1].styles,
+            [(0, ContentStyle::new()), (2, red())]
+        );
+        pager.push_ansi("e\n");
+        assert_eq!(texts(&pager), ["a", "bcd", "e"]);
+        assert_eq!(pager.lines[2].styles, [(0, red())]);
+    }
+
+    #[test]
+    fn keep_last_drops_the_first_lines() {
+        let mut pager = Pager::ansi("output".into(), "a\nb\nc\nb\n");
+        pager.search("b");
+        pager.keep_last(2);
+        assert_eq!(texts(&pager), ["c", "b"]);
+        assert_eq!(
+            pager.search_status().as_deref(),
+            Some("/b (1 matching lines)")
+        );
+    }
+
+    #[test]
+    fn set_text_keeps_the_view() {
+        let mut pager = Pager::new("list".into(), "a\nb\nc\nd\n");
+        pager.resize(2);
+        pager.last();
+        pager.search("c");
+        pager.set_text("a\nb\nc\nd\ne\n");
+        assert_eq!(pager.visible(), 2..4);
+        assert_eq!(
+            pager.search_status().as_deref(),
+            Some("/c (1 of 1 matching lines)")
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    env,
+    io::{self, Read, Write},
+    path,
+    sync::mpsc,
+    thread,
+};
+
+use crossterm::{
+    event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::{Attribute, Color, ContentStyle},
+};
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::shell;
+
+/// Lines kept above the screen of the terminal.
+const SCROLLBACK_LEN: usize = 1000;
+
+/// A shell running in a pseudo-terminal, with its screen emulated.
+pub struct Pty {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    /// What the shell printed, read in another thread.
+    rx: mpsc::Receiver<Vec<u8>>,
+    parser: vt100::Parser,
+    /// Directory the shell has to move to, once it's not running anything.
+    pending_dir: Option<path::PathBuf>,
+    /// Keys were typed since the last line was entered, so the shell could have half a line in its input.
+    typed: bool,
+}
+
+impl Pty {
+    /// Starts `$SHELL` in `dir`, with a screen of `rows` by `cols`.
+    pub fn spawn(dir: &path::Path, rows: u16, cols: u16) -> Result<Self, String> {
+        let size = PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let pair = portable_pty::native_pty_system()
+            .openpty(size)
+            .map_err(|e| e.to_string())?;
+
+        let shell = env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into());
+        let mut command = CommandBuilder::new(shell);
+        command.cwd(dir);
+        // What the screen is emulated as.
+        command.env("TERM", "xterm-256color");
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| e.to_string())?;
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            // It ends when the shell exits, or we are gone.
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                if tx.send(buf[..n].to_vec()).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            child,
+            rx,
+            parser: vt100::Parser::new(rows, cols, SCROLLBACK_LEN),
+            pending_dir: None,
+            typed: false,
+        })
+    }
+
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        if self.parser.screen().size() == (rows, cols) {
+            return;
+        }
+        self.parser.screen_mut().set_size(rows, cols);
+        let _ = self.master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        });
+    }
+
+    /// Moves the shell to `dir` as soon as it is waiting for input with nothing typed,
+    /// not to type into what it runs nor into a line the user didn't enter yet.
+    pub fn set_dir(&mut self, dir: &path::Path) {
+        self.pending_dir = Some(dir.to_path_buf());
+    }
+
+    /// Updates the screen with what the shell printed.
+    /// Returns how the shell exited, once it does.
+    pub fn poll(&mut self) -> Option<String> {
+        while let Ok(bytes) = self.rx.try_recv() {
+            self.parser.process(&bytes);
+        }
+
+        if let Ok(Some(status)) = self.child.try_wait() {
+            return Some(match status.signal() {
+                Some(signal) => format!("killed by {signal}"),
+                None => format!("exit {code}", code = status.exit_code()),
+            });
+        }
+
+        // Only the shell itself reads commands, not what it's running.
+        let is_idle = self.master.process_group_leader().is_some_and(|pgid| {
+            self.child
+                .process_id()
+                .is_some_and(|pid| pid as libc::pid_t == pgid)
+        });
+        if is_idle && !self.typed {
+            if let Some(dir) = self.pending_dir.take() {
+                // The leading space keeps it out of the history.
+                let cd = format!(" cd -- {dir}\r", dir = shell::quote(&dir.to_string_lossy()));
+                let _ = self.write(cd.as_bytes());
+            }
+        }
+        None
+    }
+
+    /// Types `key` into the terminal.
+    pub fn key(&mut self, key: &KeyEvent) -> io::Result<()> {
+        if key.kind == KeyEventKind::Release {
+            return Ok(());
+        }
+        let Some(bytes) = key_bytes(key, self.parser.screen().application_cursor()) else {
+            return Ok(());
+        };
+        // Enter, Ctrl-C and Ctrl-U leave the input empty.
+        self.typed = !matches!(bytes.as_slice(), b"\r" | b"\x03" | b"\x15");
+        self.write(&bytes)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.writer.flush()
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// How `cell` is drawn.
+pub fn cell_style(cell: &vt100::Cell) -> ContentStyle {
+    let color = |color| match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(idx) => Some(Color::AnsiValue(idx)),
+        vt100::Color::Rgb(r, g, b) => Some(Color::Rgb { r, g, b }),
+    };
+
+    let mut style = ContentStyle::new();
+    style.foreground_color = color(cell.fgcolor());
+    style.background_color = color(cell.bgcolor());
+    for (is_set, attribute) in [
+        (cell.bold(), Attribute::Bold),
+        (cell.dim(), Attribute::Dim),
+        (cell.italic(), Attribute::Italic),
+        (cell.underline(), Attribute::Underlined),
+        (cell.inverse(), Attribute::Reverse),
+    ] {
+        if is_set {
+            style.attributes.set(attribute);
+        }
+    }
+    style
+}
+
+/// What a terminal sends for `key`, like xterm does.
+/// `application_cursor` is a mode programs set to get other sequences for the arrows.
+fn key_bytes(key: &KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let cursor = |ch: char| {
+        if application_cursor {
+            format!("\x1bO{ch}")
+        } else {
+            format!("\x1b[{ch}")
+        }
+    };
+
+    let mut bytes = match key.code {
+        KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let control = match ch {
+                'a'..='z' | 'A'..='Z' => ch.to_ascii_uppercase() as u8 - b'@',
+                '@' | ' ' => 0,
+                '[' => 0x1b,
+                '\\' => 0x1c,
+                ']' => 0x1d,
+                '^' => 0x1e,
+                '_' => 0x1f,
+                _ => return None,
+            };
+            vec![control]
+        }
+        KeyCode::Char(ch) => ch.to_string().into_bytes(),
+        KeyCode::Enter => b"\r".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => cursor('A').into_bytes(),
+        KeyCode::Down => cursor('B').into_bytes(),
+        KeyCode::Right => cursor('C').into_bytes(),
+        KeyCode::Left => cursor('D').into_bytes(),
+        KeyCode::Home => cursor('H').into_bytes(),
+        KeyCode::End => cursor('F').into_bytes(),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => format!("\x1bO{ch}", ch = (b'P' + n - 1) as char).into_bytes(),
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][usize::from(n - 5)];
+            format!("\x1b[{code}~").into_bytes()
+        }
+        _ => return None,
+    };
+
+    // Alt is sent as an escape before the key.
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
@@ -0,0 +1,875 @@
+use std::{env, fs, os::unix::process::ExitStatusExt, path, process};
+
+use crate::config::{parse_bool, Config};
+
+/// A character of a word, and if it can be a wildcard (it wasn't quoted nor escaped).
+type PatternChar = (char, bool);
+
+/// What marks a line of command mode as interactive.
+const INTERACTIVE_PREFIX: char = '!';
+
+/// How many lines the history keeps by default.
+const HISTORY_SIZE_DEFAULT: usize = 1000;
+
+/// How command mode runs commands.
+#[derive(Debug)]
+pub struct Settings {
+    /// Programs that always run interactively, as if their line started with `!`.
+    pub interactive: Vec<String>,
+    /// Wait for a key after an interactive command ends, so what it printed can be read.
+    pub wait_for_key: bool,
+    /// Lines kept in the history, `0` to keep none.
+    pub history_size: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            interactive: Vec::new(),
+            wait_for_key: false,
+            history_size: HISTORY_SIZE_DEFAULT,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the `[commands]` section of the config:
+    /// ```text
+    /// [commands]
+    /// interactive = vim less htop
+    /// wait_for_key = true
+    /// history_size = 1000
+    /// ```
+    /// Settings with errors are left as default.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut errors = Vec::new();
+        for (key, value) in config.section("commands").into_iter().flatten() {
+            let result = match key.as_str() {
+                "interactive" => {
+                    settings.interactive = value.split_whitespace().map(str::to_owned).collect();
+                    Ok(())
+                }
+                "wait_for_key" => parse_bool(value).map(|b| settings.wait_for_key = b),
+                "history_size" => value
+                    .parse()
+                    .map(|n| settings.history_size = n)
+                    .map_err(|_| format!("invalid history_size `{value}`, expected a number")),
+                _ => Err(format!("unknown setting `{key}`")),
+            };
+            if let Err(e) = result {
+                errors.push(format!("[commands] {e}"));
+            }
+        }
+        (settings, errors)
+    }
+
+    /// If the program of the line `input` is one of `interactive`.
+    /// It's the first word, even if the line is run by the shell, skipping assignments like `VAR=value`.
+    pub fn is_interactive(&self, input: &str) -> bool {
+        let Ok(words) = tokenize(input) else {
+            return false;
+        };
+        let program = words
+            .iter()
+            .map(|word| {
+                // Up to where the shell would end the command, eg.: `vim;` or `(vim`.
+                word.iter()
+                    .skip_while(|&&(ch, unquoted)| unquoted && ch == '(')
+                    .take_while(|&&(ch, unquoted)| !(unquoted && "|&;<>()".contains(ch)))
+                    .map(|&(ch, _)| ch)
+                    .collect::<String>()
+            })
+            .find(|word| !word.is_empty() && !is_assignment(word));
+        program.is_some_and(|program| {
+            path::Path::new(&program)
+                .file_name()
+                .is_some_and(|name| self.interactive.iter().any(|i| *name == **i))
+        })
+    }
+}
+
+/// If `word` sets a variable for the command, like `VAR=value`.
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(var, _)| {
+        !var.is_empty()
+            && var
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    })
+}
+
+/// Splits the `!` off of interactive lines.
+pub fn strip_interactive_prefix(input: &str) -> (&str, bool) {
+    match input.trim_start().strip_prefix(INTERACTIVE_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (input, false),
+    }
+}
+
+/// Environment variables with the selection, for commands run by the shell.
+/// `DUNE_MARKED` has one name per line.
+const SELECTION_VARS: [&str; 3] = ["DUNE_DIR", "DUNE_SELECTED", "DUNE_MARKED"];
+
+/// Builds the command for a line of command mode, run in `dir`.
+/// Lines with pipes, redirections, `&&`, `;`, subshells or command substitutions are handed to `$SHELL -c`,
+/// with the selection exported, the rest are split by `parse` and run directly.
+/// `None` if the line has no command.
+pub fn command(
+    input: &str,
+    placeholders: &Placeholders,
+    dir: &path::Path,
+) -> Result<Option<process::Command>, String> {
+    let input = expand_placeholders(input, placeholders)?;
+    let mut command = if needs_shell(&input) {
+        let shell = env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into());
+        let mut command = process::Command::new(shell);
+        command.arg("-c").arg(input);
+        command
+    } else {
+        let words = parse(&input, dir)?;
+        let Some((program, args)) = words.split_first() else {
+            return Ok(None);
+        };
+        let mut command = process::Command::new(program);
+        command.args(args);
+        command
+    };
+
+    let values = [
+        placeholders.dir.to_owned(),
+        placeholders.selected.unwrap_or_default().to_owned(),
+        placeholders.marked.join("\n"),
+    ];
+    command
+        .current_dir(dir)
+        .envs(SELECTION_VARS.into_iter().zip(values));
+    Ok(Some(command))
+}
+
+/// How an interactive command went.
+pub struct Output {
+    /// The command as it would be typed.
+    pub command: String,
+    /// Eg.: `exit 1`, `killed by SIGSEGV`
+    pub status: String,
+    pub success: bool,
+}
+
+/// Runs `command` to completion attached to the terminal, so the user can interact with it.
+/// The terminal has to be given back to the normal mode before.
+pub fn run_attached(command: &mut process::Command) -> Result<Output, String> {
+    extern "C" fn ignore(_: libc::c_int) {}
+
+    let pretty_command = pretty(command);
+    // Ctrl-C and Ctrl-\ are for the command, they would kill us too since we share the terminal.
+    // A handler instead of ignoring them, as ignored signals would be ignored by the command too.
+    // SAFETY: The handler does nothing, so it can't break anything wherever it interrupts us.
+    let previous = [libc::SIGINT, libc::SIGQUIT]
+        .map(|signal| unsafe { libc::signal(signal, ignore as *const () as libc::sighandler_t) });
+    let status = command.status();
+    for (signal, handler) in [libc::SIGINT, libc::SIGQUIT].into_iter().zip(previous) {
+        // SAFETY: Puts back what was there before.
+        unsafe { libc::signal(signal, handler) };
+    }
+
+    let status =
+        status.map_err(|e| format!("Tried to run `{pretty_command}`, but failed because {e}"))?;
+    Ok(Output {
+        command: pretty_command,
+        status: describe_status(status),
+        success: status.success(),
+    })
+}
+
+/// The command as it would be typed.
+pub fn pretty(command: &process::Command) -> String {
+    [command.get_program()]
+        .into_iter()
+        .chain(command.get_args())
+        .map(|word| quote(&word.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Eg.: `exit 1`, `killed by SIGSEGV (core dumped)`
+pub fn describe_status(status: process::ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit {code}");
+    }
+    let Some(signal) = status.signal() else {
+        return status.to_string();
+    };
+
+    let name = signal_name(signal).map_or_else(|| format!("signal {signal}"), str::to_owned);
+    if status.core_dumped() {
+        format!("killed by {name} (core dumped)")
+    } else {
+        format!("killed by {name}")
+    }
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGTRAP => "SIGTRAP",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGALRM => "SIGALRM",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCHLD => "SIGCHLD",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGTTIN => "SIGTTIN",
+        libc::SIGTTOU => "SIGTTOU",
+        libc::SIGURG => "SIGURG",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        libc::SIGVTALRM => "SIGVTALRM",
+        libc::SIGPROF => "SIGPROF",
+        libc::SIGWINCH => "SIGWINCH",
+        libc::SIGSYS => "SIGSYS",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// If `input` has any of `|`, `&`, `;`, `<`, `>`, `(`, `)` or `` ` `` outside of quotes, or `$(` outside of single quotes.
+fn needs_shell(input: &str) -> bool {
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+            _ if in_single_quotes => {}
+            '"' => in_double_quotes = !in_double_quotes,
+            '\\' => {
+                chars.next();
+            }
+            '`' => return true,
+            '$' if chars.peek() == Some(&'(') => return true,
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' if !in_double_quotes => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Splits `input` into words like a POSIX shell would:
+/// - Words are separated by any amount of whitespace.
+/// - `'...'` is taken literally, `"..."` too but expanding variables and allowing `\"`, `\\`, `\$` and ``\` ``.
+/// - `\` outside of quotes takes the next character literally.
+/// - `~` at the start of a word is the home directory.
+/// - `$VAR` and `${VAR}` are the environment variables, empty if not set. Their value is not split into words.
+/// - Words with `*`, `?` or `[...]` are replaced by the paths they match, relative to `dir`.
+///   If nothing matches, the word is kept as is.
+pub fn parse(input: &str, dir: &path::Path) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    for word in tokenize(input)? {
+        if word
+            .iter()
+            .any(|&(ch, wildcard)| wildcard && is_wildcard(ch))
+        {
+            let matches = glob(&word, dir);
+            if !matches.is_empty() {
+                words.extend(matches);
+                continue;
+            }
+        }
+        words.push(word.iter().map(|(ch, _)| ch).collect());
+    }
+    Ok(words)
+}
+
+/// What the placeholders of a command expand to.
+pub struct Placeholders<'a> {
+    /// `%d`
+    pub dir: &'a str,
+    /// Name of the selected entry, `%f`.
+    /// `%n` and `%e` are its name without extension and its extension.
+    pub selected: Option<&'a str>,
+    /// Names of the marked entries, or the selected one if none is, `%F`.
+    pub marked: Vec<&'a str>,
+}
+
+/// Replaces the placeholders in `input` with their values quoted, so they are taken literally by `parse`, or a shell.
+/// Placeholders in single quotes or escaped with `\` are left as they are, `%%` is a literal `%`.
+pub fn expand_placeholders(input: &str, placeholders: &Placeholders) -> Result<String, String> {
+    let selected = || {
+        placeholders
+            .selected
+            .ok_or_else(|| "`%f`, `%n` and `%e` need a selected entry".to_owned())
+    };
+
+    let mut expanded = String::with_capacity(input.len());
+    let mut in_single_quotes = false;
+    let mut in_double_quotes = false;
+    let mut chars = input.chars();
+    while let Some(ch) = chars.next() {
+        if in_single_quotes {
+            in_single_quotes = ch != '\'';
+            expanded.push(ch);
+            continue;
+        }
+
+        let values = match ch {
+            '\'' if !in_double_quotes => {
+                in_single_quotes = true;
+                expanded.push(ch);
+                continue;
+            }
+            '"' => {
+                in_double_quotes = !in_double_quotes;
+                expanded.push(ch);
+                continue;
+            }
+            '\\' => {
+                expanded.push(ch);
+                expanded.extend(chars.next());
+                continue;
+            }
+            '%' => match chars.next() {
+                Some('%') => vec!["%"],
+                Some('d') => vec![placeholders.dir],
+                Some('f') => vec![selected()?],
+                Some('n') => vec![split_extension(selected()?).0],
+                Some('e') => vec![split_extension(selected()?).1],
+                Some('F') => placeholders.marked.clone(),
+                // Not a placeholder, eg.: `date +%Y`
+                other => {
+                    expanded.push(ch);
+                    expanded.extend(other);
+                    continue;
+                }
+            },
+            ch => {
+                expanded.push(ch);
+                continue;
+            }
+        };
+
+        let quoted = values
+            .into_iter()
+            .map(|value| {
+                if in_double_quotes {
+                    escape_in_double_quotes(value)
+                } else {
+                    quote(value)
+                }
+            })
+            .collect::<Vec<_>>();
+        expanded.push_str(&quoted.join(" "));
+    }
+
+    Ok(expanded)
+}
+
+/// Quotes `s` so a shell takes it as a single literal word.
+pub fn quote(s: &str) -> String {
+    let is_safe = |ch: char| ch.is_ascii_alphanumeric() || "_-+=.,:/@%".contains(ch);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        return s.to_owned();
+    }
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Escapes what is special inside of double quotes, so `"{s}"` is `s` literally.
+pub fn escape_in_double_quotes(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if matches!(ch, '"' | '\\' | '$' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// `archive.tar.gz` is `archive.tar` and `gz`, dot files without another dot have no extension.
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, extension),
+        _ => (name, ""),
+    }
+}
+
+fn is_wildcard(ch: char) -> bool {
+    matches!(ch, '*' | '?' | '[')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Vec<PatternChar>>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<Vec<PatternChar>> = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(Vec::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => word.push((ch, false)),
+                        None => return Err("missing closing `'`".to_owned()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(Vec::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => {
+                            word.push((chars.next().expect("just peeked"), false));
+                        }
+                        Some('$') => {
+                            let value = variable(&mut chars)?;
+                            word.extend(value.chars().map(|ch| (ch, false)));
+                        }
+                        Some(ch) => word.push((ch, false)),
+                        None => return Err("missing closing `\"`".to_owned()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(ch) => word.get_or_insert_with(Vec::new).push((ch, false)),
+                None => return Err("nothing to escape after `\\`".to_owned()),
+            },
+            '~' if word.is_none()
+                && chars
+                    .peek()
+                    .is_none_or(|ch| *ch == '/' || ch.is_whitespace()) =>
+            {
+                let home = env::var("HOME").unwrap_or_else(|_| "~".to_owned());
+                word = Some(home.chars().map(|ch| (ch, false)).collect());
+            }
+            '$' => {
+                let value = variable(&mut chars)?;
+                word.get_or_insert_with(Vec::new)
+                    .extend(value.chars().map(|ch| (ch, false)));
+            }
+            ch => word.get_or_insert_with(Vec::new).push((ch, true)),
+        }
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Reads the name of the variable after a `$`, and returns its value.
+/// A `$` not followed by a name is taken literally.
+fn variable(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    let mut name = String::new();
+    if chars.next_if_eq(&'{').is_some() {
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(ch) => name.push(ch),
+                None => return Err("missing closing `}`".to_owned()),
+            }
+        }
+    } else {
+        while let Some(ch) = chars.next_if(|ch| ch.is_ascii_alphanumeric() || *ch == '_') {
+            name.push(ch);
+        }
+        if name.is_empty() {
+            return Ok("$".to_owned());
+        }
+    }
+
+    Ok(env::var(&name).unwrap_or_default())
+}
+
+/// Paths matching `pattern`, sorted, relative to `dir` unless the pattern is absolute.
+fn glob(pattern: &[PatternChar], dir: &path::Path) -> Vec<String> {
+    let components = pattern
+        .split(|&(ch, _)| ch == '/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+
+    let is_absolute = pattern.first().is_some_and(|&(ch, _)| ch == '/');
+    let (base, shown) = if is_absolute {
+        (path::PathBuf::from("/"), "/".to_owned())
+    } else {
+        (dir.to_path_buf(), String::new())
+    };
+
+    // Pairs of the real path, and how the user wrote it.
+    let mut candidates = vec![(base, shown)];
+    for (i, component) in components.iter().enumerate() {
+        let is_last = i == components.len() - 1;
+        let join = |shown: &str, name: &str| {
+            let separator = if shown.is_empty() || shown.ends_with('/') {
+                ""
+            } else {
+                "/"
+            };
+            format!("{shown}{separator}{name}")
+        };
+
+        if !component
+            .iter()
+            .any(|&(ch, wildcard)| wildcard && is_wildcard(ch))
+        {
+            let name = component.iter().map(|(ch, _)| ch).collect::<String>();
+            candidates = candidates
+                .into_iter()
+                .map(|(path, shown)| (path.join(&name), join(&shown, &name)))
+                .filter(|(path, _)| path.symlink_metadata().is_ok())
+                .collect();
+            continue;
+        }
+
+        let mut next = Vec::new();
+        for (path, shown) in candidates {
+            let Ok(read_dir) = fs::read_dir(&path) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let name_chars = name.chars().collect::<Vec<_>>();
+                // Like shells, hidden files only match if the pattern starts with a dot.
+                if name.starts_with('.') && component[0].0 != '.' {
+                    continue;
+                }
+                if !is_last && !entry.path().is_dir() {
+                    continue;
+                }
+                if wildcard_match(component, &name_chars) {
+                    next.push((entry.path(), join(&shown, &name)));
+                }
+            }
+        }
+        candidates = next;
+    }
+
+    let mut matches = candidates
+        .into_iter()
+        .map(|(_, shown)| shown)
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches
+}
+
+/// Matches `name` against a pattern with `*`, `?` and `[...]` (`[!...]` or `[^...]` to negate).
+/// When what follows a `*` doesn't match, it's retried one character later from the last `*` only,
+/// so it never takes more than `pattern.len() * name.len()` steps.
+fn wildcard_match(pattern: &[PatternChar], name: &[char]) -> bool {
+    let mut p = 0;
+    let mut n = 0;
+    // Where the pattern continues after the last `*`, and where that was tried from in the name.
+    let mut last_star = None;
+    while n < name.len() {
+        if pattern.get(p) == Some(&('*', true)) {
+            p += 1;
+            last_star = Some((p, n));
+            continue;
+        }
+        if let Some(len) = match_one(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+            continue;
+        }
+        let Some((star_p, star_n)) = last_star else {
+            return false;
+        };
+        // The `*` takes one more character.
+        p = star_p;
+        n = star_n + 1;
+        last_star = Some((star_p, n));
+    }
+    pattern[p..].iter().all(|&pc| pc == ('*', true))
+}
+
+/// If `ch` matches the start of `pattern`, which is not a `*`, how many of its characters it took.
+fn match_one(pattern: &[PatternChar], ch: char) -> Option<usize> {
+    let (&(first, wildcard), rest) = pattern.split_first()?;
+    let matched = match first {
+        '?' if wildcard => true,
+        '[' if wildcard => match class_end(rest) {
+            Some(end) => return in_class(&rest[..end], ch).then_some(end + 2),
+            // Not closed, it's just a `[`.
+            None => ch == '[',
+        },
+        first => ch == first,
+    };
+    matched.then_some(1)
+}
+
+/// Index of the `]` closing a class, the first character can be a `]` without closing it.
+fn class_end(class: &[PatternChar]) -> Option<usize> {
+    let start = match class.first() {
+        Some(('!' | '^', _)) => 2,
+        _ => 1,
+    };
+    class
+        .iter()
+        .skip(start)
+        .position(|&(ch, _)| ch == ']')
+        .map(|i| i + start)
+}
+
+fn in_class(class: &[PatternChar], ch: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some((('!' | '^', _), rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1].0 == '-' {
+            found |= (class[i].0..=class[i + 2].0).contains(&ch);
+            i += 3;
+        } else {
+            found |= class[i].0 == ch;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Result<Vec<String>, String> {
+        let words = tokenize(input)?;
+        Ok(words
+            .iter()
+            .map(|word| word.iter().map(|(ch, _)| ch).collect())
+            .collect())
+    }
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern = pattern.chars().map(|ch| (ch, true)).collect::<Vec<_>>();
+        wildcard_match(&pattern, &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn tokenize_splits_on_whitespace() {
+        assert_eq!(
+            words("ls  -la\tsrc "),
+            Ok(vec!["ls".into(), "-la".into(), "src".into()])
+        );
+        assert_eq!(words("   "), Ok(vec![]));
+    }
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(
+            words(r#"echo 'a b' "c d" e\ f 'it'\''s' "x\"y\\z" "" "#),
+            Ok(vec![
+                "echo".into(),
+                "a b".into(),
+                "c d".into(),
+                "e f".into(),
+                "it's".into(),
+                r#"x"y\z"#.into(),
+                "".into(),
+            ])
+        );
+        // Only some characters can be escaped inside of double quotes.
+        assert_eq!(words(r#""a\b""#), Ok(vec![r"a\b".into()]));
+    }
+
+    #[test]
+    fn tokenize_variables() {
+        assert_eq!(
+            words("a$DUNE_TEST_UNSET_VARb ${DUNE_TEST_UNSET_VAR}c $ \"$\""),
+            Ok(vec!["a".into(), "c".into(), "$".into(), "$".into()])
+        );
+        assert!(words("${DUNE_TEST_UNSET_VAR").is_err());
+    }
+
+    #[test]
+    fn tokenize_marks_quoted_characters_as_not_wildcards() {
+        let words = tokenize(r#"*'*'"*"\*"#).unwrap();
+        assert_eq!(
+            words,
+            [vec![('*', true), ('*', false), ('*', false), ('*', false)]]
+        );
+    }
+
+    #[test]
+    fn tokenize_fails_on_unclosed_quotes() {
+        assert!(words("echo 'a").is_err());
+        assert!(words("echo \"a").is_err());
+        assert!(words("echo a\\").is_err());
+    }
+
+    #[test]
+    fn wildcard_match_stars_and_question_marks() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "abbbc"));
+        assert!(matches("*ab*ab", "xabyabab"));
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", ".txt"));
+        assert!(!matches("a*", "ba"));
+    }
+
+    #[test]
+    fn wildcard_match_classes() {
+        assert!(matches("[abc].rs", "b.rs"));
+        assert!(!matches("[abc].rs", "d.rs"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(matches("[!a-c]x", "dx"));
+        assert!(matches("[^a-c]x", "dx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches("[]]", "]"));
+        // Not closed, it's just a `[`.
+        assert!(matches("[ab", "[ab"));
+    }
+
+    #[test]
+    fn wildcard_match_many_stars_is_fast() {
+        let name = "a".repeat(100);
+        assert!(!matches(&format!("{}b", "a*".repeat(50)), &name));
+        assert!(matches(&"a*".repeat(50), &name));
+    }
+
+    #[test]
+    fn parse_expands_wildcards() {
+        let dir = env::temp_dir().join(format!("dune-test-glob-{}", process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for file in ["a.rs", "b.rs", "c.txt", ".hidden.rs", "src/main.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let parse = |input| parse(input, &dir).unwrap();
+        assert_eq!(parse("ls *.rs"), ["ls", "a.rs", "b.rs"]);
+        assert_eq!(parse("ls .*.rs"), ["ls", ".hidden.rs"]);
+        assert_eq!(parse("ls */*.rs"), ["ls", "src/main.rs"]);
+        assert_eq!(parse("ls '*.rs' *.md"), ["ls", "*.rs", "*.md"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn placeholders() -> Placeholders<'static> {
+        Placeholders {
+            dir: "/home/me/my dir",
+            selected: Some("notes.tar.gz"),
+            marked: vec!["a b", "it's"],
+        }
+    }
+
+    #[test]
+    fn expand_placeholders_quotes_values() {
+        let expand = |input| expand_placeholders(input, &placeholders()).unwrap();
+        assert_eq!(expand("ls %d"), "ls '/home/me/my dir'");
+        assert_eq!(expand("tar xf %f"), "tar xf notes.tar.gz");
+        assert_eq!(expand("echo %n %e"), "echo notes.tar gz");
+        assert_eq!(expand("rm %F"), r"rm 'a b' 'it'\''s'");
+    }
+
+    #[test]
+    fn expand_placeholders_in_double_quotes() {
+        let placeholders = Placeholders {
+            selected: Some(r#"say "$hi""#),
+            ..placeholders()
+        };
+        assert_eq!(
+            expand_placeholders(r#"echo "%f""#, &placeholders).unwrap(),
+            r#"echo "say \"\$hi\"""#
+        );
+    }
+
+    #[test]
+    fn expand_placeholders_leaves_the_rest() {
+        let expand = |input| expand_placeholders(input, &placeholders()).unwrap();
+        assert_eq!(expand("echo '%f' \\%f 100%% %"), "echo '%f' \\%f 100% %");
+        assert_eq!(expand("date +%Y"), "date +%Y");
+    }
+
+    #[test]
+    fn expand_placeholders_needs_a_selection() {
+        let placeholders = Placeholders {
+            selected: None,
+            marked: Vec::new(),
+            ..placeholders()
+        };
+        assert!(expand_placeholders("cat %f", &placeholders).is_err());
+        assert_eq!(
+            expand_placeholders("rm %F", &placeholders),
+            Ok("rm ".into())
+        );
+    }
+
+    #[test]
+    fn split_extension_keeps_dot_files_whole() {
+        assert_eq!(split_extension("a.tar.gz"), ("a.tar", "gz"));
+        assert_eq!(split_extension(".bashrc"), (".bashrc", ""));
+        assert_eq!(split_extension("Makefile"), ("Makefile", ""));
+    }
+
+    #[test]
+    fn needs_shell_for_shell_syntax() {
+        for input in [
+            "ls | wc -l",
+            "make && make install",
+            "a; b",
+            "cat < in > out",
+            "(cd src)",
+            "echo `date`",
+            "echo $(date)",
+            "echo \"$(date)\"",
+        ] {
+            assert!(needs_shell(input), "{input}");
+        }
+    }
+
+    #[test]
+    fn needs_shell_not_for_quoted_or_escaped_syntax() {
+        for input in [
+            "ls -la",
+            "echo 'a | b'",
+            "echo \"a; b\"",
+            "echo a\\|b",
+            "echo '$(date)'",
+            "echo $HOME",
+        ] {
+            assert!(!needs_shell(input), "{input}");
+        }
+    }
+
+    #[test]
+    fn is_interactive_by_the_first_word() {
+        let settings = Settings {
+            interactive: vec!["vim".into(), "htop".into()],
+            ..Settings::default()
+        };
+        for (input, interactive) in [
+            ("vim notes.txt", true),
+            ("/usr/bin/vim", true),
+            ("'vim' a", true),
+            ("EDITOR=nano vim a", true),
+            ("vim $(git ls-files) | cat", true),
+            ("(htop)", true),
+            ("vimdiff a b", false),
+            ("git log | vim -", false),
+            ("", false),
+        ] {
+            assert_eq!(settings.is_interactive(input), interactive, "{input}");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+/// How sizes are shown to the user.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, ...
+    #[default]
+    Iec,
+    /// Powers of 1000: kB, MB, ...
+    Si,
+    /// The exact amount of bytes.
+    Bytes,
+}
+
+const IEC_UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const SI_UNITS: [&str; 6] = ["kB", "MB", "GB", "TB", "PB", "EB"];
+
+impl SizeUnits {
+    /// `size` in bytes, with one decimal in the biggest unit that keeps it under 1000.
+    /// Sizes under the base of the units are in bytes, so IEC ones go up to `1023 B`.
+    /// Eg.: `512 B`, `1.5 KiB`, `20.0 MB`
+    pub fn format(self, size: u64) -> String {
+        let (base, units) = match self {
+            SizeUnits::Iec => (1024.0, IEC_UNITS),
+            SizeUnits::Si => (1000.0, SI_UNITS),
+            SizeUnits::Bytes => return size.to_string(),
+        };
+
+        if (size as f64) < base {
+            return format!("{size} B");
+        }
+
+        let mut value = size as f64 / base;
+        for unit in &units[..units.len() - 1] {
+            // Rounded, so `999.96` doesn't become `1000.0`.
+            if value < 999.95 {
+                return format!("{value:.1} {unit}");
+            }
+            value /= base;
+        }
+        format!("{value:.1} {unit}", unit = units[units.len() - 1])
+    }
+}
+
+impl FromStr for SizeUnits {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iec" => Ok(SizeUnits::Iec),
+            "si" => Ok(SizeUnits::Si),
+            "bytes" => Ok(SizeUnits::Bytes),
+            _ => Err(format!(
+                "invalid units `{s}`, expected `iec`, `si` or `bytes`"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_iec() {
+        assert_eq!(SizeUnits::Iec.format(0), "0 B");
+        assert_eq!(SizeUnits::Iec.format(1023), "1023 B");
+        assert_eq!(SizeUnits::Iec.format(1024), "1.0 KiB");
+        assert_eq!(SizeUnits::Iec.format(1536), "1.5 KiB");
+        assert_eq!(SizeUnits::Iec.format(20 * 1024 * 1024), "20.0 MiB");
+        // Rounding to `1000.0 KiB` would be wider than the rest.
+        assert_eq!(SizeUnits::Iec.format(1024 * 1000 - 1), "1.0 MiB");
+        assert_eq!(SizeUnits::Iec.format(u64::MAX), "16.0 EiB");
+    }
+
+    #[test]
+    fn format_si() {
+        assert_eq!(SizeUnits::Si.format(999), "999 B");
+        assert_eq!(SizeUnits::Si.format(1000), "1.0 kB");
+        assert_eq!(SizeUnits::Si.format(999_949), "999.9 kB");
+        assert_eq!(SizeUnits::Si.format(999_950), "1.0 MB");
+        assert_eq!(SizeUnits::Si.format(1_500_000_000), "1.5 GB");
+    }
+
+    #[test]
+    fn format_bytes() {
+        assert_eq!(SizeUnits::Bytes.format(0), "0");
+        assert_eq!(SizeUnits::Bytes.format(1_234_567), "1234567");
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("iec".parse(), Ok(SizeUnits::Iec));
+        assert_eq!("si".parse(), Ok(SizeUnits::Si));
+        assert_eq!("bytes".parse(), Ok(SizeUnits::Bytes));
+        assert!("kb".parse::<SizeUnits>().is_err());
+    }
+}
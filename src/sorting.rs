@@ -0,0 +1,282 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::{
+    config::{parse_bool, Config},
+    file_info::FileInfo,
+    users::UserNames,
+};
+
+/// What the entries are compared by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    /// Like name, but numbers are compared by value: `file2` < `file10`.
+    Natural,
+    Extension,
+    /// Biggest first, like `ls -S`.
+    Size,
+    /// Last modified, oldest first.
+    Date,
+    Owner,
+}
+
+impl SortKey {
+    /// The order they are cycled through.
+    const ALL: [SortKey; 6] = [
+        SortKey::Name,
+        SortKey::Natural,
+        SortKey::Extension,
+        SortKey::Size,
+        SortKey::Date,
+        SortKey::Owner,
+    ];
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|k| *k == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Natural => "natural",
+            SortKey::Extension => "extension",
+            SortKey::Size => "size",
+            SortKey::Date => "date",
+            SortKey::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|k| k.name() == s)
+            .ok_or_else(|| format!("unknown sort key `{s}`"))
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(self.name())
+    }
+}
+
+/// How the entries are ordered.
+/// The switches are independent of the key, and of each other.
+#[derive(Debug)]
+pub struct Sorting {
+    pub key: SortKey,
+    /// Ignore case when comparing names, extensions and owners.
+    pub case_insensitive: bool,
+    /// Reverses the key only, dirs first and hidden last still apply.
+    pub reverse: bool,
+    pub dirs_first: bool,
+    pub hidden_last: bool,
+}
+
+impl Default for Sorting {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            case_insensitive: false,
+            reverse: false,
+            dirs_first: true,
+            hidden_last: true,
+        }
+    }
+}
+
+impl Sorting {
+    /// Reads the `[sorting]` section of the config:
+    /// ```text
+    /// [sorting]
+    /// key = natural
+    /// case_insensitive = true
+    /// reverse = false
+    /// dirs_first = true
+    /// hidden_last = false
+    /// ```
+    /// Settings with errors are left as default.
+    pub fn from_config(config: &Config) -> (Self, Vec<String>) {
+        let mut sorting = Self::default();
+        let mut errors = Vec::new();
+        for (key, value) in config.section("sorting").into_iter().flatten() {
+            let result = match key.as_str() {
+                "key" => value.parse().map(|k| sorting.key = k),
+                "case_insensitive" => parse_bool(value).map(|b| sorting.case_insensitive = b),
+                "reverse" => parse_bool(value).map(|b| sorting.reverse = b),
+                "dirs_first" => parse_bool(value).map(|b| sorting.dirs_first = b),
+                "hidden_last" => parse_bool(value).map(|b| sorting.hidden_last = b),
+                _ => Err(format!("unknown setting `{key}`")),
+            };
+            if let Err(e) = result {
+                errors.push(format!("[sorting] {e}"));
+            }
+        }
+        (sorting, errors)
+    }
+
+    pub fn sort(&self, entries: &mut [FileInfo], users: &mut UserNames) {
+        entries.sort_by(|l, r| self.compare(l, r, users));
+    }
+
+    fn compare(&self, l: &FileInfo, r: &FileInfo, users: &mut UserNames) -> Ordering {
+        let is_hidden = |e: &FileInfo| e.name().starts_with('.');
+        if self.hidden_last && is_hidden(l) != is_hidden(r) {
+            return is_hidden(l).cmp(&is_hidden(r));
+        }
+        if self.dirs_first && l.is_dir() != r.is_dir() {
+            return r.is_dir().cmp(&l.is_dir());
+        }
+
+        let ordering = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Natural => natural_cmp(l.name(), r.name(), self.case_insensitive),
+            SortKey::Extension => self.text_cmp(extension(l), extension(r)),
+            SortKey::Size => r.size().cmp(&l.size()),
+            SortKey::Date => l.last_modified().cmp(&r.last_modified()),
+            SortKey::Owner => self.text_cmp(&users.user(l.uid()), &users.user(r.uid())),
+        }
+        // Entries that are the same for the key are still sorted by name.
+        .then_with(|| self.text_cmp(l.name(), r.name()));
+
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    fn text_cmp(&self, l: &str, r: &str) -> Ordering {
+        if self.case_insensitive {
+            l.to_lowercase()
+                .cmp(&r.to_lowercase())
+                .then_with(|| l.cmp(r))
+        } else {
+            l.cmp(r)
+        }
+    }
+}
+
+impl fmt::Display for Sorting {
+    /// Eg.: `natural (i) ↓ dirs first`
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(fmt, "{key}", key = self.key)?;
+        if self.case_insensitive {
+            fmt.write_str(" (i)")?;
+        }
+        fmt.write_str(if self.reverse { " ↓" } else { " ↑" })?;
+        if self.dirs_first {
+            fmt.write_str(" dirs first")?;
+        }
+        if self.hidden_last {
+            fmt.write_str(" hidden last")?;
+        }
+        Ok(())
+    }
+}
+
+/// The extension of files, directories and dot files without one have none.
+fn extension(entry: &FileInfo) -> &str {
+    if entry.is_dir() {
+        return "";
+    }
+    match entry.name().rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext,
+        _ => "",
+    }
+}
+
+/// Compares runs of digits by their value, and the rest as text.
+/// Eg.: `file2` < `file10`, `v1.9` < `v1.10`
+fn natural_cmp(l: &str, r: &str, case_insensitive: bool) -> Ordering {
+    let mut l_chunks = chunks(l);
+    let mut r_chunks = chunks(r);
+    loop {
+        let (l_chunk, r_chunk) = match (l_chunks.next(), r_chunks.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) => (l, r),
+        };
+
+        let is_number = |s: &str| s.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = if is_number(l_chunk) && is_number(r_chunk) {
+            let l_digits = l_chunk.trim_start_matches('0');
+            let r_digits = r_chunk.trim_start_matches('0');
+            l_digits
+                .len()
+                .cmp(&r_digits.len())
+                .then_with(|| l_digits.cmp(r_digits))
+                // Same value, fewer leading zeros first.
+                .then_with(|| l_chunk.len().cmp(&r_chunk.len()))
+        } else if case_insensitive {
+            l_chunk.to_lowercase().cmp(&r_chunk.to_lowercase())
+        } else {
+            l_chunk.cmp(r_chunk)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Splits `s` into runs of digits and runs of everything else.
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str], case_insensitive: bool) -> Vec<String> {
+        let mut names = names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        names.sort_by(|l, r| natural_cmp(l, r, case_insensitive));
+        names
+    }
+
+    #[test]
+    fn natural_cmp_compares_numbers_by_value() {
+        assert_eq!(
+            sorted(&["file10", "file2", "file1", "file"], false),
+            ["file", "file1", "file2", "file10"]
+        );
+        assert_eq!(
+            sorted(&["v1.10", "v1.9", "v1.9.1", "v10"], false),
+            ["v1.9", "v1.9.1", "v1.10", "v10"]
+        );
+        assert_eq!(natural_cmp("99", "100", false), Ordering::Less);
+        assert_eq!(
+            natural_cmp("18446744073709551616", "18446744073709551615", false),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn natural_cmp_puts_fewer_leading_zeros_first() {
+        assert_eq!(sorted(&["a002", "a2", "a02"], false), ["a2", "a02", "a002"]);
+        assert_eq!(natural_cmp("a01", "a2", false), Ordering::Less);
+    }
+
+    #[test]
+    fn natural_cmp_case() {
+        assert_eq!(natural_cmp("B", "a", false), Ordering::Less);
+        assert_eq!(natural_cmp("B", "a", true), Ordering::Greater);
+        assert_eq!(natural_cmp("File2", "file2", true), Ordering::Equal);
+    }
+}
@@ -0,0 +1,54 @@
+use std::{collections::HashMap, fs};
+
+/// Resolves user and group ids into names, from `/etc/passwd` and `/etc/group`.
+/// The files are only read the first time a name is needed.
+#[derive(Default)]
+pub struct UserNames {
+    users: Option<HashMap<u32, String>>,
+    groups: Option<HashMap<u32, String>>,
+}
+
+impl UserNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name of the user, or the id itself if it has no name.
+    pub fn user(&mut self, uid: u32) -> String {
+        self.users
+            .get_or_insert_with(|| parse_ids("/etc/passwd"))
+            .get(&uid)
+            .cloned()
+            .unwrap_or_else(|| uid.to_string())
+    }
+
+    /// Name of the group, or the id itself if it has no name.
+    pub fn group(&mut self, gid: u32) -> String {
+        self.groups
+            .get_or_insert_with(|| parse_ids("/etc/group"))
+            .get(&gid)
+            .cloned()
+            .unwrap_or_else(|| gid.to_string())
+    }
+}
+
+/// If the file can't be read, no names are resolved.
+fn parse_ids(path: &str) -> HashMap<u32, String> {
+    fs::read_to_string(path)
+        .map(|contents| parse_id_lines(&contents))
+        .unwrap_or_default()
+}
+
+/// Both files have the format `name:password:id:...`.
+/// Like `getent`, when an id is repeated the first name wins.
+fn parse_id_lines(contents: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in contents.lines().filter(|line| !line.starts_with('#')) {
+        let mut fields = line.split(':');
+        let (Some(name), Some(Ok(id))) = (fields.next(), fields.nth(1).map(str::parse)) else {
+            continue;
+        };
+        names.entry(id).or_insert_with(|| name.to_owned());
+    }
+    names
+}
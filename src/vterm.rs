@@ -5,7 +5,7 @@ use std::{
 };
 
 use crossterm::{
-    cursor, execute, queue,
+    cursor, event, execute, queue,
     style::{self, ContentStyle},
     terminal::{self, ClearType},
 };
@@ -35,6 +35,8 @@ pub struct VTerm {
 impl VTerm {
     pub fn new() -> Self {
         terminal::enable_raw_mode().expect("could not enable raw mode");
+        execute!(stdout(), terminal::EnterAlternateScreen)
+            .expect("could not enter the alternate screen");
         let (w, h) = terminal::size().expect("could not get terminal size");
         let (w, h) = (w as usize, h as usize);
         queue!(stdout(), cursor::MoveTo(0, 0)).expect("could not move cursor");
@@ -72,6 +74,42 @@ impl VTerm {
         )
     }
 
+    /// Immediately gives the terminal back to its normal mode, for programs that need it.
+    pub fn suspend() -> io::Result<()> {
+        execute!(stdout(), terminal::LeaveAlternateScreen, cursor::Show)?;
+        terminal::disable_raw_mode()
+    }
+
+    /// Immediately takes the terminal back after `suspend`.
+    /// Everything has to be drawn again, and it might have been resized meanwhile.
+    pub fn resume(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout(),
+            terminal::EnterAlternateScreen,
+            terminal::Clear(ClearType::All),
+            cursor::Hide
+        )?;
+        let (w, h) = terminal::size()?;
+        self.width = w as usize;
+        self.height = h as usize;
+        self.queue_empty();
+        Ok(())
+    }
+
+    /// Prints `message` and blocks until a key is pressed, while suspended.
+    pub fn wait_for_key(message: &str) -> io::Result<()> {
+        execute!(stdout(), style::Print(format!("\n{message}")))?;
+        terminal::enable_raw_mode()?;
+        loop {
+            if let event::Event::Key(key) = event::read()? {
+                if key.kind == event::KeyEventKind::Press {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
     /// Gets the terminal size
     pub fn size(&self) -> (usize, usize) {
         (self.width, self.height)
@@ -128,6 +166,17 @@ impl VTerm {
         Ok(())
     }
 
+    /// Not attached to the terminal, for tests.
+    #[cfg(test)]
+    pub fn detached(width: usize, height: usize) -> Self {
+        VTerm {
+            vterminal_last: Self::new_empty_vterminal(width, height),
+            vterminal: Self::new_empty_vterminal(width, height),
+            width,
+            height,
+        }
+    }
+
     fn new_empty_vterminal(width: usize, height: usize) -> Vec<Cell> {
         vec![Cell::new(); width * height]
     }
@@ -139,7 +188,12 @@ impl VTerm {
 
 impl Drop for VTerm {
     fn drop(&mut self) {
-        let _ = execute!(stdout(), terminal::Clear(ClearType::All), cursor::Show);
+        let _ = execute!(
+            stdout(),
+            terminal::Clear(ClearType::All),
+            terminal::LeaveAlternateScreen,
+            cursor::Show
+        );
         crossterm::terminal::disable_raw_mode().expect("could not disable raw mode");
     }
 }